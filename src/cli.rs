@@ -0,0 +1,477 @@
+use glam::Vec2;
+use serde::Serialize;
+
+use platformer_jump_checker::{
+    graph_bake::GraphBake,
+    jump_profile::JumpProfile,
+    jump_solver::{JumpLaunch, JumpResult, JumpSolver},
+    level::Level,
+    pathfinding::{
+        init_pathfinding_graph, GraphBuildReport, Pathfinding, PathfindingGraphConnectionType,
+    },
+    surface_path::SurfacePath,
+    svg_export::export_svg,
+};
+
+pub const USAGE: &str = "\
+Usage:
+    platformer-jump-checker [LEVEL] [--profile PROFILE] [--graph GRAPH]
+        Open the level in the interactive debug view
+
+    platformer-jump-checker stats [--level LEVEL] [--profile PROFILE] [--graph GRAPH]
+        Build the navigation graph and print statistics about it as JSON
+
+    platformer-jump-checker check-jump <FROM> <TO> [--level LEVEL] [--profile PROFILE] [--graph GRAPH]
+        Print the jump between two graph nodes as JSON
+
+    platformer-jump-checker path <FROM> <TO> [--level LEVEL] [--profile PROFILE] [--graph GRAPH]
+        Print the cheapest path between two graph nodes as JSON, or between two X,Y positions
+        which are snapped onto the closest walkable lines
+
+    platformer-jump-checker export-svg [--level LEVEL] [--profile PROFILE] [--graph GRAPH]
+        Print an SVG drawing of the level polygons and navigation graph
+
+    platformer-jump-checker bake <OUTPUT> [--level LEVEL] [--profile PROFILE]
+        Build the navigation graph and save it to OUTPUT, to be loaded later with --graph
+
+Levels and profiles are JSON files, the built in level and default profile are used when they are left out.
+A graph baked for a different level or profile is rejected.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    /// Open the interactive debug view
+    Window,
+    Stats,
+    CheckJump {
+        from: usize,
+        to: usize,
+    },
+    Path {
+        from: usize,
+        to: usize,
+    },
+    PathBetweenPoints {
+        from: Vec2,
+        to: Vec2,
+    },
+    Bake {
+        output_path: String,
+    },
+    ExportSvg,
+}
+
+#[derive(Debug, Clone)]
+pub struct CliArgs {
+    pub command: CliCommand,
+    pub level_path: Option<String>,
+    pub profile_path: Option<String>,
+    /// A baked graph to load instead of building one
+    pub graph_path: Option<String>,
+}
+
+/// Parses the command line arguments, not including the name of the binary
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, String> {
+    let mut args = args.into_iter().peekable();
+
+    let subcommand = match args.peek().map(String::as_str) {
+        Some("stats") | Some("check-jump") | Some("path") | Some("bake") | Some("export-svg") => {
+            args.next()
+        }
+        _ => None,
+    };
+
+    let mut level_path = None;
+    let mut profile_path = None;
+    let mut graph_path = None;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--level" => {
+                level_path = Some(args.next().ok_or("Expected a level path after --level")?);
+            }
+            "--profile" => {
+                profile_path = Some(
+                    args.next()
+                        .ok_or("Expected a jump profile path after --profile")?,
+                );
+            }
+            "--graph" => {
+                graph_path = Some(args.next().ok_or("Expected a graph path after --graph")?);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option \"{}\"", flag));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match subcommand.as_deref() {
+        None => {
+            // The debug view takes the level as its only positional argument
+            if positional.len() > 1 {
+                return Err(format!("Unexpected argument \"{}\"", positional[1]));
+            }
+            if let Some(positional_level_path) = positional.pop() {
+                level_path = Some(positional_level_path);
+            }
+
+            CliCommand::Window
+        }
+        Some(subcommand @ ("stats" | "export-svg")) => {
+            if let Some(arg) = positional.first() {
+                return Err(format!("Unexpected argument \"{}\"", arg));
+            }
+
+            if subcommand == "stats" {
+                CliCommand::Stats
+            } else {
+                CliCommand::ExportSvg
+            }
+        }
+        Some("bake") => {
+            let [output_path] = positional.as_slice() else {
+                return Err("bake expects an <OUTPUT> path".to_string());
+            };
+            if graph_path.is_some() {
+                return Err("bake always builds the graph, it can't take --graph".to_string());
+            }
+
+            CliCommand::Bake {
+                output_path: output_path.clone(),
+            }
+        }
+        Some(subcommand) => {
+            let [from, to] = positional.as_slice() else {
+                return Err(format!(
+                    "{} expects a <FROM> and a <TO> node id",
+                    subcommand
+                ));
+            };
+
+            if subcommand == "path" && (from.contains(',') || to.contains(',')) {
+                return Ok(CliArgs {
+                    command: CliCommand::PathBetweenPoints {
+                        from: parse_position(from)?,
+                        to: parse_position(to)?,
+                    },
+                    level_path,
+                    profile_path,
+                    graph_path,
+                });
+            }
+
+            let from = parse_node_id(from)?;
+            let to = parse_node_id(to)?;
+
+            if subcommand == "check-jump" {
+                CliCommand::CheckJump { from, to }
+            } else {
+                CliCommand::Path { from, to }
+            }
+        }
+    };
+
+    Ok(CliArgs {
+        command,
+        level_path,
+        profile_path,
+        graph_path,
+    })
+}
+
+fn parse_node_id(arg: &str) -> Result<usize, String> {
+    arg.parse()
+        .map_err(|_| format!("\"{}\" is not a valid node id", arg))
+}
+
+fn parse_position(arg: &str) -> Result<Vec2, String> {
+    let invalid_position = || format!("\"{}\" is not a valid X,Y position", arg);
+
+    let (x, y) = arg.split_once(',').ok_or_else(invalid_position)?;
+    let x = x.trim().parse().map_err(|_| invalid_position())?;
+    let y = y.trim().parse().map_err(|_| invalid_position())?;
+
+    Ok(Vec2::new(x, y))
+}
+
+#[derive(Serialize)]
+struct GraphStats {
+    polygon_count: usize,
+    line_count: usize,
+    node_count: usize,
+    corner_count: usize,
+    walkable_connection_count: usize,
+    jumpable_connection_count: usize,
+    droppable_connection_count: usize,
+    /// Left out when the graph was loaded from a bake
+    #[serde(skip_serializing_if = "Option::is_none")]
+    build: Option<GraphBuildReport>,
+}
+
+#[derive(Serialize)]
+struct JumpQuery {
+    from: usize,
+    to: usize,
+    #[serde(flatten)]
+    jump: JumpResult,
+}
+
+#[derive(Serialize)]
+struct PathHop {
+    node_id: usize,
+    position: Vec2,
+    connection_type: PathfindingGraphConnectionType,
+    dist: f32,
+    effort: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jump: Option<JumpLaunch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    drop_velocity: Option<Vec2>,
+}
+
+#[derive(Serialize)]
+struct PathQuery {
+    from: usize,
+    to: usize,
+    found: bool,
+    cost: Option<f32>,
+    hops: Vec<PathHop>,
+}
+
+#[derive(Serialize)]
+struct BakeSummary<'a> {
+    output_path: &'a str,
+    version: u32,
+    level_hash: u64,
+    profile_hash: u64,
+    node_count: usize,
+}
+
+#[derive(Serialize)]
+struct SurfacePathQuery {
+    from: Vec2,
+    to: Vec2,
+    found: bool,
+    #[serde(flatten)]
+    path: Option<SurfacePath>,
+}
+
+/// Builds the navigation graph, or loads `graph_bake`, without opening a window and answers a single query,
+/// returning its JSON, or SVG for `ExportSvg`
+pub fn run_headless(
+    command: &CliCommand,
+    level: &Level,
+    profile: &JumpProfile,
+    graph_bake: Option<GraphBake>,
+) -> Result<String, String> {
+    let mut pathfinding = Pathfinding::default();
+    let build_report = match graph_bake {
+        Some(graph_bake) => {
+            graph_bake.apply(&mut pathfinding);
+            None
+        }
+        None => Some(init_pathfinding_graph(level, profile, &mut pathfinding)),
+    };
+
+    let check_node_id = |node_id: usize| {
+        if node_id < pathfinding.nodes.len() {
+            Ok(node_id)
+        } else {
+            Err(format!(
+                "Node {} doesn't exist, the graph has {} nodes",
+                node_id,
+                pathfinding.nodes.len()
+            ))
+        }
+    };
+
+    let json = match *command {
+        CliCommand::ExportSvg => return Ok(export_svg(level, profile, &pathfinding)),
+        CliCommand::Bake { ref output_path } => {
+            let graph_bake = GraphBake::new(&pathfinding, level, profile);
+            graph_bake
+                .save(output_path)
+                .map_err(|err| format!("Failed to bake graph to \"{}\": {}", output_path, err))?;
+
+            serde_json::to_string_pretty(&BakeSummary {
+                output_path,
+                version: graph_bake.version,
+                level_hash: graph_bake.level_hash,
+                profile_hash: graph_bake.profile_hash,
+                node_count: graph_bake.nodes.len(),
+            })
+        }
+        CliCommand::Window => return Err("The debug view can't be run headless".to_string()),
+        CliCommand::Stats => serde_json::to_string_pretty(&GraphStats {
+            polygon_count: level.polygons.len(),
+            line_count: level
+                .polygons
+                .iter()
+                .map(|polygon| polygon.points.len().saturating_sub(1))
+                .sum(),
+            node_count: pathfinding.nodes.len(),
+            corner_count: pathfinding
+                .nodes
+                .iter()
+                .filter(|node| node.is_corner)
+                .count(),
+            walkable_connection_count: pathfinding
+                .nodes
+                .iter()
+                .map(|node| node.walkable_connections.len())
+                .sum(),
+            jumpable_connection_count: pathfinding
+                .nodes
+                .iter()
+                .map(|node| node.jumpable_connections.len())
+                .sum(),
+            droppable_connection_count: pathfinding
+                .nodes
+                .iter()
+                .map(|node| node.droppable_connections.len())
+                .sum(),
+            build: build_report,
+        }),
+        CliCommand::CheckJump { from, to } => {
+            let start_node = &pathfinding.nodes[check_node_id(from)?];
+            let goal_node = &pathfinding.nodes[check_node_id(to)?];
+
+            let mut ignored_lines = start_node.polygon_lines();
+            ignored_lines.extend(goal_node.polygon_lines());
+
+            let jump = JumpSolver::new(profile).solve(
+                start_node.position,
+                goal_node.position,
+                level,
+                &ignored_lines,
+            );
+
+            serde_json::to_string_pretty(&JumpQuery { from, to, jump })
+        }
+        CliCommand::PathBetweenPoints { from, to } => {
+            let path = pathfinding.find_path_between_points(level, profile, from, to);
+
+            serde_json::to_string_pretty(&SurfacePathQuery {
+                from,
+                to,
+                found: path.is_some(),
+                path,
+            })
+        }
+        CliCommand::Path { from, to } => {
+            let path = pathfinding.find_path(check_node_id(from)?, check_node_id(to)?);
+
+            let hops = path
+                .iter()
+                .flat_map(|path| path.connections.iter())
+                .map(|connection| PathHop {
+                    node_id: connection.node_id,
+                    position: pathfinding.nodes[connection.node_id].position,
+                    connection_type: connection.connection_type.clone(),
+                    dist: connection.dist,
+                    effort: connection.effort,
+                    jump: connection.jump,
+                    drop_velocity: connection.drop_velocity,
+                })
+                .collect();
+
+            serde_json::to_string_pretty(&PathQuery {
+                from,
+                to,
+                found: path.is_some(),
+                cost: path.as_ref().map(|path| path.cost),
+                hops,
+            })
+        }
+    };
+
+    json.map_err(|err| format!("Failed to write JSON: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_each_subcommand() {
+        assert_eq!(parse(&[]).unwrap().command, CliCommand::Window);
+        assert_eq!(parse(&["stats"]).unwrap().command, CliCommand::Stats);
+        assert_eq!(
+            parse(&["export-svg"]).unwrap().command,
+            CliCommand::ExportSvg
+        );
+        assert_eq!(
+            parse(&["check-jump", "3", "7"]).unwrap().command,
+            CliCommand::CheckJump { from: 3, to: 7 }
+        );
+        assert_eq!(
+            parse(&["path", "3", "7"]).unwrap().command,
+            CliCommand::Path { from: 3, to: 7 }
+        );
+        assert_eq!(
+            parse(&["path", "-16,208", "112.5, 208"]).unwrap().command,
+            CliCommand::PathBetweenPoints {
+                from: Vec2::new(-16.0, 208.0),
+                to: Vec2::new(112.5, 208.0),
+            }
+        );
+        assert_eq!(
+            parse(&["bake", "graph.json"]).unwrap().command,
+            CliCommand::Bake {
+                output_path: "graph.json".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn takes_the_debug_view_level_as_a_positional_argument() {
+        let cli_args = parse(&["level.json", "--graph", "graph.json"]).unwrap();
+
+        assert_eq!(cli_args.command, CliCommand::Window);
+        assert_eq!(cli_args.level_path.as_deref(), Some("level.json"));
+        assert_eq!(cli_args.graph_path.as_deref(), Some("graph.json"));
+    }
+
+    #[test]
+    fn rejects_a_flag_missing_its_value() {
+        for flag in ["--level", "--profile", "--graph"] {
+            assert!(parse(&["stats", flag]).is_err(), "{} without a value", flag);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert!(parse(&["--levle", "level.json"]).is_err());
+        assert!(parse(&["stats", "--verbose"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_positional_arguments() {
+        assert!(parse(&["stats", "extra"]).is_err());
+        assert!(parse(&["check-jump", "3"]).is_err());
+        assert!(parse(&["check-jump", "three", "7"]).is_err());
+        assert!(parse(&["path", "1,2", "3"]).is_err());
+        assert!(parse(&["bake"]).is_err());
+        assert!(parse(&["bake", "out.json", "--graph", "graph.json"]).is_err());
+    }
+
+    #[test]
+    fn loads_the_profile_path() {
+        let profile_path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/jump_profile.json");
+        let cli_args = parse(&["stats", "--profile", profile_path]).unwrap();
+
+        assert_eq!(cli_args.profile_path.as_deref(), Some(profile_path));
+
+        let profile = JumpProfile::load(cli_args.profile_path.unwrap()).unwrap();
+        assert_eq!(
+            profile.max_launch_speed,
+            JumpProfile::default().max_launch_speed
+        );
+        assert_eq!(profile.gravity, JumpProfile::default().gravity);
+    }
+}
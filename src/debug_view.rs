@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::window::PrimaryWindow;
+use bevy::{app::AppExit, window::PresentMode};
+use platformer_jump_checker::{
+    graph_bake::GraphBake,
+    jump_check::JumpCheckPlugin,
+    jump_profile::JumpProfile,
+    jump_solver::JumpSolver,
+    level::Level,
+    pathfinding::{
+        init_pathfinding_graph, Pathfinding, PathfindingGraphConnectionType, PathfindingPlugin,
+    },
+};
+
+/// Opens the interactive debug view of the level, building its graph unless a baked one is given
+pub fn run_debug_view(level: Level, profile: JumpProfile, graph_bake: Option<GraphBake>) {
+    let mut pathfinding = Pathfinding::default();
+    if let Some(graph_bake) = graph_bake {
+        graph_bake.apply(&mut pathfinding);
+    }
+
+    App::new()
+        .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
+        .insert_resource(pathfinding)
+        .insert_resource(level)
+        .insert_resource(profile)
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Platformer Jump Checker".to_string(),
+                present_mode: PresentMode::AutoVsync,
+                focused: true,
+                ..default()
+            }),
+            ..default()
+        }))
+        .add_plugins(PathfindingPlugin)
+        .add_plugins(JumpCheckPlugin)
+        .init_resource::<DebugOverlays>()
+        // Startup systems
+        .add_systems(Startup, s_init)
+        // Update systems
+        .add_systems(Update, s_input)
+        .add_systems(Update, s_camera_controls)
+        .add_systems(Update, s_render)
+        .add_systems(Update, s_toggle_overlays)
+        .add_systems(Update, s_render_overlays)
+        .run();
+}
+
+pub fn s_init(
+    mut commands: Commands,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    level: Res<Level>,
+    profile: Res<JumpProfile>,
+    mut pathfinding: ResMut<Pathfinding>,
+) {
+    // A baked graph was loaded already
+    if pathfinding.nodes.is_empty() {
+        let build_report = init_pathfinding_graph(&level, &profile, &mut pathfinding);
+        println!("{}", build_report);
+    }
+
+    let mut camera_bundle = Camera2dBundle::default();
+    fit_level_in_window(
+        &level,
+        q_windows.single(),
+        &mut camera_bundle.transform,
+        &mut camera_bundle.projection,
+    );
+
+    commands.spawn(camera_bundle);
+}
+
+pub fn s_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut exit: EventWriter<AppExit>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection)>,
+    mut pathfinding: ResMut<Pathfinding>,
+) {
+    // Escape to exit (if not WASM)
+    #[cfg(not(target_arch = "wasm32"))]
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit);
+    }
+
+    let (camera, camera_transform, projection) = q_camera.single();
+
+    let Some(mouse_pos_world) = q_windows
+        .single()
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world_2d(camera_transform, position))
+    else {
+        return;
+    };
+
+    // The pick radii are in screen pixels, so nodes are as easy to click at any zoom
+    let pick_scale = projection.scale;
+
+    // Select start node with left click
+    if mouse_input.just_pressed(MouseButton::Left) {
+        if let Some(node) = pathfinding.nearest_node(mouse_pos_world, 3.5 * pick_scale, |_| true) {
+            pathfinding.start_graph_node = Some(node.clone());
+        }
+
+        pathfinding.update_path();
+    }
+    // Select goal node with right click
+    if mouse_input.just_pressed(MouseButton::Right) {
+        if let Some(node) = pathfinding.nearest_node(mouse_pos_world, 7.5 * pick_scale, |_| true) {
+            pathfinding.goal_graph_node = Some(node.clone());
+        }
+
+        pathfinding.update_path();
+    }
+}
+
+/// How much one notch of the mouse wheel zooms in or out
+const ZOOM_STEP: f32 = 1.1;
+/// How many pixels of smooth scrolling, like on a touchpad, count as one notch of the mouse wheel
+const PIXELS_PER_SCROLL_NOTCH: f32 = 100.0;
+const MIN_ZOOM_SCALE: f32 = 0.05;
+const MAX_ZOOM_SCALE: f32 = 50.0;
+
+/// Zooms around the cursor with the mouse wheel, pans with the middle mouse button and fits the level with F
+pub fn s_camera_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    level: Res<Level>,
+) {
+    let window = q_windows.single();
+    let (mut camera_transform, mut projection) = q_camera.single_mut();
+
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        fit_level_in_window(&level, window, &mut camera_transform, &mut projection);
+    }
+
+    // Screen space has y pointing down, world space has it pointing up
+    let screen_to_world = |offset: Vec2, scale: f32| Vec2::new(offset.x, -offset.y) * scale;
+
+    let mouse_motion: Vec2 = mouse_motion_events.read().map(|event| event.delta).sum();
+    if mouse_input.pressed(MouseButton::Middle) {
+        let pan = screen_to_world(mouse_motion, projection.scale);
+        camera_transform.translation -= pan.extend(0.0);
+    }
+
+    let scroll_notches: f32 = mouse_wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_SCROLL_NOTCH,
+        })
+        .sum();
+    if scroll_notches != 0.0 {
+        let old_scale = projection.scale;
+        let new_scale =
+            (old_scale * ZOOM_STEP.powf(-scroll_notches)).clamp(MIN_ZOOM_SCALE, MAX_ZOOM_SCALE);
+
+        // Keep the point under the cursor in place while zooming
+        if let Some(cursor_position) = window.cursor_position() {
+            let cursor_offset = cursor_position - Vec2::new(window.width(), window.height()) / 2.0;
+            let shift = screen_to_world(cursor_offset, old_scale - new_scale);
+            camera_transform.translation += shift.extend(0.0);
+        }
+
+        projection.scale = new_scale;
+    }
+}
+
+/// Centers the camera on the level and zooms it so the whole level fits in the window
+fn fit_level_in_window(
+    level: &Level,
+    window: &Window,
+    camera_transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+) {
+    let window_size = Vec2::new(window.width(), window.height());
+
+    // The level is built around the origin
+    camera_transform.translation = Vec3::new(0.0, 0.0, camera_transform.translation.z);
+
+    if let Some(scale) = fitted_zoom_scale(level, window_size) {
+        projection.scale = scale;
+    }
+}
+
+/// The zoom scale at which the whole level fits in a window of `window_size` pixels
+fn fitted_zoom_scale(level: &Level, window_size: Vec2) -> Option<f32> {
+    if window_size.x <= 0.0 || window_size.y <= 0.0 {
+        return None;
+    }
+
+    // The level size counts tiles, so scale it up to world units
+    let level_extent = level.size * level.grid_size;
+    let scale = (level_extent / window_size).max_element();
+
+    Some(scale.clamp(MIN_ZOOM_SCALE, MAX_ZOOM_SCALE))
+}
+
+pub fn s_render(mut gizmos: Gizmos, level: Res<Level>, pathfinding: Res<Pathfinding>) {
+    // Draw the level polygons
+    for polygon in &level.polygons {
+        gizmos.linestrip_2d(
+            polygon.points.iter().cloned().collect::<Vec<Vec2>>(),
+            Color::rgba(polygon.color[0], polygon.color[1], polygon.color[2], 0.1),
+        );
+    }
+
+    // Draw the pathfinding nodes
+    for node in &pathfinding.nodes {
+        gizmos.circle_2d(node.position, 2.5, Color::WHITE);
+    }
+
+    // Draw a larger circle for the start and end nodes
+    if let Some(start_graph_node) = &pathfinding.start_graph_node {
+        gizmos.circle_2d(start_graph_node.position, 2.5, Color::GREEN);
+    }
+    if let Some(goal_graph_node) = &pathfinding.goal_graph_node {
+        gizmos.circle_2d(goal_graph_node.position, 2.5, Color::YELLOW);
+    }
+
+    // Draw the path between the start and goal nodes
+    if let Some(path) = &pathfinding.path {
+        let mut prev_pos = pathfinding.nodes[path.start_node_id].position;
+
+        for connection in &path.connections {
+            let pos = pathfinding.nodes[connection.node_id].position;
+
+            let color = match connection.connection_type {
+                PathfindingGraphConnectionType::Walkable => Color::CYAN,
+                PathfindingGraphConnectionType::Jumpable => Color::ORANGE,
+                PathfindingGraphConnectionType::Droppable => Color::PURPLE,
+            };
+
+            gizmos.line_2d(prev_pos, pos, color);
+
+            prev_pos = pos;
+        }
+    }
+}
+
+/// The parts of the navigation graph drawn on top of the level, each toggled with a number key
+#[derive(Resource, Default)]
+pub struct DebugOverlays {
+    pub walkable_connections: bool,
+    pub jumpable_connections: bool,
+    pub droppable_connections: bool,
+    pub normals: bool,
+    pub corners: bool,
+}
+
+pub fn s_toggle_overlays(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlays: ResMut<DebugOverlays>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Digit1) {
+        overlays.walkable_connections = !overlays.walkable_connections;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit2) {
+        overlays.jumpable_connections = !overlays.jumpable_connections;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit3) {
+        overlays.droppable_connections = !overlays.droppable_connections;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit4) {
+        overlays.normals = !overlays.normals;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit5) {
+        overlays.corners = !overlays.corners;
+    }
+}
+
+pub fn s_render_overlays(
+    mut gizmos: Gizmos,
+    profile: Res<JumpProfile>,
+    pathfinding: Res<Pathfinding>,
+    overlays: Res<DebugOverlays>,
+) {
+    let jump_solver = JumpSolver::new(&profile);
+
+    for node in &pathfinding.nodes {
+        if overlays.walkable_connections {
+            for connection in &node.walkable_connections {
+                gizmos.line_2d(
+                    node.position,
+                    pathfinding.nodes[connection.node_id].position,
+                    Color::CYAN.with_a(0.5),
+                );
+            }
+        }
+
+        // Jumps go from green for the gentlest to red for ones at the fastest launch speed
+        if overlays.jumpable_connections {
+            for connection in &node.jumpable_connections {
+                let goal_position = pathfinding.nodes[connection.node_id].position;
+                let Some(jump) = connection.jump else {
+                    continue;
+                };
+
+                let effort = (connection.effort / profile.fastest_launch_speed()).clamp(0.0, 1.0);
+
+                gizmos.linestrip_2d(
+                    jump_solver.sample_trajectory(node.position, goal_position, &jump),
+                    Color::rgba(effort, 1.0 - effort, 0.0, 0.3),
+                );
+            }
+        }
+
+        if overlays.droppable_connections {
+            for connection in &node.droppable_connections {
+                gizmos.line_2d(
+                    node.position,
+                    pathfinding.nodes[connection.node_id].position,
+                    Color::PURPLE,
+                );
+            }
+        }
+
+        if overlays.normals {
+            gizmos.line_2d(
+                node.position,
+                node.position + node.normal * 8.0,
+                Color::BLUE,
+            );
+        }
+
+        // External corners are red and internal corners are yellow
+        if overlays.corners && node.is_corner {
+            let color = if node.is_external_corner == Some(true) {
+                Color::RED
+            } else {
+                Color::YELLOW
+            };
+
+            gizmos.circle_2d(node.position, 5.0, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use platformer_jump_checker::level::DEFAULT_LEVEL_DATA;
+
+    #[test]
+    fn fitted_zoom_scale_fits_the_default_level() {
+        let level = Level::from_json(DEFAULT_LEVEL_DATA, crate::GRID_SIZE).unwrap();
+        let window_size = Vec2::new(1280.0, 720.0);
+
+        let scale = fitted_zoom_scale(&level, window_size).unwrap();
+
+        // 19x21 tiles of 32 units, so the 672 unit height is the tight side
+        assert!((scale - 672.0 / 720.0).abs() < 1e-5, "scale {}", scale);
+        let visible = window_size * scale;
+        let level_extent = level.size * level.grid_size;
+        assert!(visible.x >= level_extent.x - 1e-3 && visible.y >= level_extent.y - 1e-3);
+    }
+
+    #[test]
+    fn fitted_zoom_scale_ignores_a_minimised_window() {
+        let level = Level::from_json(DEFAULT_LEVEL_DATA, crate::GRID_SIZE).unwrap();
+
+        assert_eq!(fitted_zoom_scale(&level, Vec2::ZERO), None);
+    }
+}
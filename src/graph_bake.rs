@@ -0,0 +1,264 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    jump_profile::JumpProfile,
+    level::Level,
+    pathfinding::{Pathfinding, PathfindingGraphNode},
+};
+
+/// Bumped whenever the layout of a bake file changes, older bakes have to be rebaked
+pub const GRAPH_BAKE_VERSION: u32 = 5;
+
+/// A navigation graph saved to disk, so it doesn't have to be rebuilt every launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphBake {
+    pub version: u32,
+    /// The hash of the level geometry the graph was built for
+    pub level_hash: u64,
+    /// The hash of the jump profile the graph was built with
+    pub profile_hash: u64,
+    pub nodes: Vec<PathfindingGraphNode>,
+}
+
+/// Just enough of a bake file to check its version before reading the rest
+#[derive(Deserialize)]
+struct GraphBakeHeader {
+    version: u32,
+}
+
+impl GraphBake {
+    /// Captures the graph in `pathfinding`, which should have been built from `level` and `profile`
+    pub fn new(pathfinding: &Pathfinding, level: &Level, profile: &JumpProfile) -> GraphBake {
+        GraphBake {
+            version: GRAPH_BAKE_VERSION,
+            level_hash: hash_level(level),
+            profile_hash: hash_profile(profile),
+            nodes: pathfinding.nodes.clone(),
+        }
+    }
+
+    /// Reads a bake from a JSON file on disk
+    pub fn load(path: impl AsRef<Path>) -> Result<GraphBake, GraphBakeError> {
+        let bake_data = std::fs::read(path)?;
+
+        let header: GraphBakeHeader = serde_json::from_slice(&bake_data)?;
+        if header.version != GRAPH_BAKE_VERSION {
+            return Err(GraphBakeError::UnsupportedVersion {
+                found: header.version,
+            });
+        }
+
+        Ok(serde_json::from_slice(&bake_data)?)
+    }
+
+    /// Writes the bake to a JSON file on disk
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), GraphBakeError> {
+        let bake_data = serde_json::to_vec(self)?;
+
+        Ok(std::fs::write(path, bake_data)?)
+    }
+
+    /// Makes sure the bake was built for this level and profile, and not an older version of either
+    pub fn check(&self, level: &Level, profile: &JumpProfile) -> Result<(), GraphBakeError> {
+        if self.level_hash != hash_level(level) {
+            return Err(GraphBakeError::StaleLevel);
+        }
+
+        if self.profile_hash != hash_profile(profile) {
+            return Err(GraphBakeError::StaleProfile);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the graph in `pathfinding` with the baked one, clearing any path through the old graph
+    pub fn apply(self, pathfinding: &mut Pathfinding) {
+        *pathfinding = Pathfinding::from_nodes(self.nodes);
+    }
+}
+
+/// A hash of everything in the level that affects the graph.
+/// This is FNV-1a rather than `DefaultHasher`, which isn't guaranteed to stay the same between Rust releases.
+pub fn hash_level(level: &Level) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    hash = fnv1a(hash, &level.grid_size.to_bits().to_le_bytes());
+
+    // Polygon colors are random, so they're left out
+    for polygon in &level.polygons {
+        hash = fnv1a(hash, &[polygon.is_container as u8]);
+        hash = fnv1a(hash, &(polygon.points.len() as u64).to_le_bytes());
+
+        for point in &polygon.points {
+            hash = fnv1a(hash, &point.x.to_bits().to_le_bytes());
+            hash = fnv1a(hash, &point.y.to_bits().to_le_bytes());
+        }
+    }
+
+    hash
+}
+
+/// A hash of every field of the jump profile
+pub fn hash_profile(profile: &JumpProfile) -> u64 {
+    let profile_data = serde_json::to_vec(profile).expect("A jump profile should always serialize");
+
+    fnv1a(FNV_OFFSET_BASIS, &profile_data)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+#[derive(Debug)]
+pub enum GraphBakeError {
+    /// The bake file could not be read or written
+    Io(std::io::Error),
+    /// The bake file is not valid JSON, or doesn't match the bake layout
+    InvalidJson(serde_json::Error),
+    /// The bake was written by a different version of the baker
+    UnsupportedVersion { found: u32 },
+    /// The level has changed since the graph was baked
+    StaleLevel,
+    /// The jump profile has changed since the graph was baked
+    StaleProfile,
+}
+
+impl fmt::Display for GraphBakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphBakeError::Io(err) => write!(f, "could not access graph bake: {}", err),
+            GraphBakeError::InvalidJson(err) => write!(f, "invalid graph bake: {}", err),
+            GraphBakeError::UnsupportedVersion { found } => write!(
+                f,
+                "graph bake is version {}, but only version {} is supported",
+                found, GRAPH_BAKE_VERSION
+            ),
+            GraphBakeError::StaleLevel => {
+                write!(f, "graph bake was made for a different level")
+            }
+            GraphBakeError::StaleProfile => {
+                write!(f, "graph bake was made with a different jump profile")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphBakeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphBakeError::Io(err) => Some(err),
+            GraphBakeError::InvalidJson(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GraphBakeError {
+    fn from(err: std::io::Error) -> Self {
+        GraphBakeError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for GraphBakeError {
+    fn from(err: serde_json::Error) -> Self {
+        GraphBakeError::InvalidJson(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{level::DEFAULT_LEVEL_DATA, pathfinding::init_pathfinding_graph};
+
+    fn default_level() -> Level {
+        Level::from_json(DEFAULT_LEVEL_DATA, 32.0).unwrap()
+    }
+
+    fn temp_bake_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("graph_bake_{}_{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_hashes() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let level = default_level();
+        let profile = JumpProfile::default();
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &profile, &mut pathfinding);
+        assert!(!pathfinding.nodes.is_empty());
+
+        let path = temp_bake_path("round_trip");
+        GraphBake::new(&pathfinding, &level, &profile)
+            .save(&path)
+            .unwrap();
+        let loaded = GraphBake::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        loaded.check(&level, &profile).unwrap();
+        assert_eq!(loaded.version, GRAPH_BAKE_VERSION);
+
+        let mut loaded_pathfinding = Pathfinding::default();
+        loaded.apply(&mut loaded_pathfinding);
+        assert_eq!(
+            serde_json::to_value(&loaded_pathfinding.nodes).unwrap(),
+            serde_json::to_value(&pathfinding.nodes).unwrap()
+        );
+    }
+
+    #[test]
+    fn check_rejects_a_different_level_or_profile() {
+        let level = default_level();
+        let profile = JumpProfile::default();
+        let graph_bake = GraphBake::new(&Pathfinding::default(), &level, &profile);
+
+        let other_level = Level::from_json(DEFAULT_LEVEL_DATA, 16.0).unwrap();
+        assert!(matches!(
+            graph_bake.check(&other_level, &profile),
+            Err(GraphBakeError::StaleLevel)
+        ));
+
+        let other_profile = JumpProfile {
+            max_launch_speed: profile.max_launch_speed + 1.0,
+            ..profile.clone()
+        };
+        assert!(matches!(
+            graph_bake.check(&level, &other_profile),
+            Err(GraphBakeError::StaleProfile)
+        ));
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let level = default_level();
+        let mut graph_bake =
+            GraphBake::new(&Pathfinding::default(), &level, &JumpProfile::default());
+        graph_bake.version = GRAPH_BAKE_VERSION - 1;
+
+        let path = temp_bake_path("old_version");
+        graph_bake.save(&path).unwrap();
+        let loaded = GraphBake::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            loaded,
+            Err(GraphBakeError::UnsupportedVersion { found }) if found == GRAPH_BAKE_VERSION - 1
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use std::{fmt, path::Path};
+
+#[cfg(feature = "render")]
+use bevy::ecs::system::Resource;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// The physics of the jumping character, shared by every jump check.
+/// Any field missing from a profile file keeps its default value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "render", derive(Resource))]
+#[serde(default)]
+pub struct JumpProfile {
+    /// The acceleration applied to the character while in the air
+    pub gravity: Vec2,
+    /// The fastest the character can be launched, in any direction. Only used by the artillery movement model.
+    pub max_launch_speed: f32,
+    /// The horizontal speed the character runs at, used when running off ledges
+    pub run_speed: f32,
+    pub character_radius: f32,
+    /// How many segments each trajectory is split into when it is sampled for drawing
+    pub sample_count: usize,
+    /// Which of the possible flight times every jump is made with
+    pub flight_time: FlightTimeChoice,
+    /// How the launch velocity of a jump is limited
+    pub movement: MovementModel,
+    /// How the character can steer across gravity while in the air, or `None` if it can't
+    pub air_control: Option<AirControl>,
+}
+
+/// Steering in the air, which lets jumps go further across than the launch alone would take them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AirControl {
+    /// The fastest the character can speed up across gravity while in the air
+    pub max_acceleration: f32,
+    /// The fastest steering can make the character move across gravity, it never slows down a faster launch
+    pub max_speed: f32,
+}
+
+/// The limits on how the character can launch itself into a jump
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementModel {
+    /// Launched like an artillery shell, in any direction up to `max_launch_speed`
+    #[default]
+    Artillery,
+    /// A jump impulse against gravity and a separately capped horizontal speed, like most platformers.
+    /// The jump always lands on its way down. Setting both jump speeds the same gives a fixed height jump.
+    Platformer {
+        /// The weakest jump the character can do, 0 if a short hop can be as small as needed
+        min_jump_speed: f32,
+        /// The strongest jump the character can do
+        max_jump_speed: f32,
+        /// The fastest the character can move sideways while in the air
+        max_horizontal_speed: f32,
+    },
+    /// A fixed jump impulse that's cut short by letting go of the jump button, so the longer it's held
+    /// the higher the jump goes. Once it's let go gravity is multiplied by `release_gravity_multiplier`.
+    /// Like the platformer model, the jump always lands on its way down.
+    VariableHeight {
+        /// The speed every jump starts with, against gravity
+        jump_speed: f32,
+        /// The fastest the character can move sideways while in the air
+        max_horizontal_speed: f32,
+        /// The shortest tap of the jump button that registers
+        min_hold_time: f32,
+        /// How long the jump button can be held before the jump is released anyway
+        max_hold_time: f32,
+        /// How much stronger gravity gets once the jump button is let go
+        release_gravity_multiplier: f32,
+    },
+}
+
+/// Every goal in reach can be hit with any flight time between the flattest and the highest arc
+/// the movement model allows. This picks one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightTimeChoice {
+    /// The quickest jump, on the flattest arc the movement model allows
+    MinimumTime,
+    /// The jump that needs the least launch speed
+    #[default]
+    LowEnergy,
+    /// The slowest jump, on the highest arc the movement model allows
+    MaximumHeight,
+    /// An exact flight time, the goal is only reachable if it's between the other extremes
+    Custom(f32),
+    /// The jump needing the least launch speed that doesn't hit the level, searching the whole range.
+    /// Without a level to check against this is the same as `LowEnergy`.
+    CheapestClear,
+}
+
+impl Default for JumpProfile {
+    fn default() -> Self {
+        JumpProfile {
+            gravity: Vec2::new(0.0, -0.5),
+            max_launch_speed: 8.0,
+            run_speed: 3.0,
+            character_radius: 4.0,
+            sample_count: 10,
+            flight_time: FlightTimeChoice::LowEnergy,
+            movement: MovementModel::Artillery,
+            air_control: None,
+        }
+    }
+}
+
+impl JumpProfile {
+    /// Builds a jump profile from JSON, making sure its values make sense
+    pub fn from_json(profile_data: &[u8]) -> Result<JumpProfile, JumpProfileError> {
+        let profile: JumpProfile = serde_json::from_slice(profile_data)?;
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    /// Reads a jump profile from a JSON file on disk
+    pub fn load(path: impl AsRef<Path>) -> Result<JumpProfile, JumpProfileError> {
+        let profile_data = std::fs::read(path)?;
+
+        JumpProfile::from_json(&profile_data)
+    }
+
+    /// Checks for values the jump math can't work with, which would otherwise come out as NaN launches
+    pub fn validate(&self) -> Result<(), JumpProfileError> {
+        let invalid_value = |field, reason| Err(JumpProfileError::InvalidValue { field, reason });
+
+        if !self.gravity.is_finite() || self.gravity.length_squared() < f32::EPSILON {
+            return invalid_value("gravity", "must be finite and not zero");
+        }
+
+        if !is_positive(self.character_radius) {
+            return invalid_value("character_radius", "must be greater than zero");
+        }
+
+        if self.movement == MovementModel::Artillery && !is_positive(self.max_launch_speed) {
+            return invalid_value("max_launch_speed", "must be greater than zero");
+        }
+
+        if let FlightTimeChoice::Custom(flight_time) = self.flight_time {
+            if !is_positive(flight_time) {
+                return invalid_value(
+                    "flight_time",
+                    "must be greater than zero when it is a custom time",
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The fastest any jump can be launched with the movement model
+    pub fn fastest_launch_speed(&self) -> f32 {
+        match self.movement {
+            MovementModel::Artillery => self.max_launch_speed,
+            MovementModel::Platformer {
+                max_jump_speed,
+                max_horizontal_speed,
+                ..
+            } => max_jump_speed.hypot(max_horizontal_speed),
+            MovementModel::VariableHeight {
+                jump_speed,
+                max_horizontal_speed,
+                ..
+            } => jump_speed.hypot(max_horizontal_speed),
+        }
+    }
+}
+
+fn is_positive(value: f32) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+#[derive(Debug)]
+pub enum JumpProfileError {
+    /// The profile file could not be read
+    Io(std::io::Error),
+    /// The profile file is not valid JSON, or has fields of the wrong type
+    InvalidJson(serde_json::Error),
+    /// A field has a value the jump math can't work with
+    InvalidValue {
+        field: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for JumpProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JumpProfileError::Io(err) => write!(f, "could not read jump profile: {}", err),
+            JumpProfileError::InvalidJson(err) => write!(f, "invalid jump profile: {}", err),
+            JumpProfileError::InvalidValue { field, reason } => {
+                write!(f, "invalid jump profile: {} {}", field, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JumpProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JumpProfileError::Io(err) => Some(err),
+            JumpProfileError::InvalidJson(err) => Some(err),
+            JumpProfileError::InvalidValue { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for JumpProfileError {
+    fn from(err: std::io::Error) -> Self {
+        JumpProfileError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for JumpProfileError {
+    fn from(err: serde_json::Error) -> Self {
+        JumpProfileError::InvalidJson(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_field(profile_data: &str) -> Option<&'static str> {
+        match JumpProfile::from_json(profile_data.as_bytes()) {
+            Err(JumpProfileError::InvalidValue { field, .. }) => Some(field),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn accepts_the_default_profile() {
+        let profile_data = include_bytes!("../assets/jump_profile.json");
+
+        assert!(JumpProfile::from_json(profile_data).is_ok());
+        assert!(JumpProfile::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_zero_gravity() {
+        assert_eq!(invalid_field(r#"{"gravity": [0.0, 0.0]}"#), Some("gravity"));
+    }
+
+    #[test]
+    fn rejects_a_character_radius_of_zero_or_less() {
+        assert_eq!(
+            invalid_field(r#"{"character_radius": 0.0}"#),
+            Some("character_radius")
+        );
+        assert_eq!(
+            invalid_field(r#"{"character_radius": -4.0}"#),
+            Some("character_radius")
+        );
+    }
+
+    #[test]
+    fn rejects_a_max_launch_speed_of_zero_or_less() {
+        assert_eq!(
+            invalid_field(r#"{"max_launch_speed": 0.0}"#),
+            Some("max_launch_speed")
+        );
+        assert_eq!(
+            invalid_field(r#"{"max_launch_speed": -8.0}"#),
+            Some("max_launch_speed")
+        );
+    }
+
+    #[test]
+    fn rejects_a_custom_flight_time_of_zero_or_less() {
+        assert_eq!(
+            invalid_field(r#"{"flight_time": {"custom": 0.0}}"#),
+            Some("flight_time")
+        );
+        assert_eq!(
+            invalid_field(r#"{"flight_time": {"custom": -1.0}}"#),
+            Some("flight_time")
+        );
+        assert_eq!(invalid_field(r#"{"flight_time": {"custom": 20.0}}"#), None);
+    }
+}
@@ -0,0 +1,1321 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    jump_profile::{AirControl, FlightTimeChoice, JumpProfile, MovementModel},
+    level::{Level, PolygonLine, RaycastHit},
+    utils::{closest_point_on_line, swept_circle_line_contact},
+};
+
+/// How far a custom flight time can stray outside the feasible range, relative to the range,
+/// before it's out of reach. This keeps jumps right on the movement model's limits reachable.
+const FLIGHT_TIME_TOLERANCE: f32 = 1e-4;
+
+/// How many slices the flight time range is cut into when searching for an arc that clears the level
+const FLIGHT_TIME_SEARCH_STEPS: usize = 16;
+
+/// Leeway for steering that ends right on the air control's speed limit
+const STEERING_SPEED_TOLERANCE: f32 = 1e-3;
+
+/// How many flight times are tried when looking for the ones air control can reach the goal with
+const STEERED_FLIGHT_TIME_SAMPLES: usize = 32;
+
+/// How many times the ends of the steered flight time range are halved once they've been found
+const STEERED_FLIGHT_TIME_BISECTIONS: usize = 24;
+
+/// Works out whether a jump between two points is possible, and what it looks like.
+/// This is the only place the trajectory math lives, so the debug view and the graph builder always agree.
+#[derive(Debug, Clone)]
+pub struct JumpSolver {
+    pub profile: JumpProfile,
+}
+
+/// The first level line a jump runs into
+#[derive(Debug, Clone, Serialize)]
+pub struct JumpCollision {
+    pub line: PolygonLine,
+    /// The point on the line that the character touches
+    pub point: Vec2,
+    /// How far into the jump the character touches the line
+    pub time: f32,
+}
+
+/// Everything needed to follow a jump from where it starts
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct JumpLaunch {
+    pub launch_velocity: Vec2,
+    pub flight_time: f32,
+    /// How long to hold the jump button, for movement models where that sets the height of the jump
+    pub hold_time: Option<f32>,
+    /// How to steer in the air, when the launch alone doesn't go far enough across
+    pub steering: Option<AirSteering>,
+}
+
+/// Speeding up across gravity right from the launch, then coasting the rest of the way
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AirSteering {
+    pub acceleration: Vec2,
+    /// How long after the launch to stop speeding up
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JumpResult {
+    /// Whether the goal can be reached with the chosen flight time within the movement model's limits
+    pub reachable: bool,
+    /// Whether the goal is reachable and the trajectory doesn't hit the level
+    pub feasible: bool,
+    pub launch_velocity: Vec2,
+    pub flight_time: f32,
+    /// How long the jump button is held, for movement models where that sets the height of the jump
+    pub hold_time: Option<f32>,
+    /// How the character steers in the air, when the launch alone doesn't go far enough across
+    pub steering: Option<AirSteering>,
+    /// The shortest and longest flight times the movement model allows, when the goal is in reach
+    pub flight_time_range: Option<(f32, f32)>,
+    /// Evenly spaced positions along the trajectory, from the start to the goal
+    pub trajectory: Vec<Vec2>,
+    pub collision: Option<JumpCollision>,
+    /// The first line between the start and the goal. Only the flight time search looks for arcs around
+    /// what's in the way, every other flight time choice needs the start to see the goal.
+    pub line_of_sight_hit: Option<RaycastHit>,
+}
+
+impl JumpSolver {
+    pub fn new(profile: &JumpProfile) -> Self {
+        JumpSolver {
+            profile: profile.clone(),
+        }
+    }
+
+    /// Solves for the jump from `start_pos` to `goal_pos` with the profile's flight time, checking it against the level.
+    /// Unless the profile searches the flight times for a clear arc, the start has to see the goal.
+    /// Lines in `ignored_lines` (usually the ones the start and goal are standing on) are never collided with.
+    pub fn solve(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        level: &Level,
+        ignored_lines: &[PolygonLine],
+    ) -> JumpResult {
+        self.solve_with_flight_time(
+            start_pos,
+            goal_pos,
+            level,
+            ignored_lines,
+            self.profile.flight_time,
+        )
+    }
+
+    /// Like `solve`, but with the given flight time instead of the profile's
+    pub fn solve_with_flight_time(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        level: &Level,
+        ignored_lines: &[PolygonLine],
+        flight_time_choice: FlightTimeChoice,
+    ) -> JumpResult {
+        let delta_p = goal_pos - start_pos;
+
+        // There's nothing to jump over if the start and goal are the same
+        if delta_p.length_squared() < f32::EPSILON {
+            return JumpResult {
+                reachable: false,
+                feasible: false,
+                launch_velocity: Vec2::ZERO,
+                flight_time: 0.0,
+                hold_time: None,
+                steering: None,
+                flight_time_range: None,
+                trajectory: vec![start_pos],
+                collision: None,
+                line_of_sight_hit: None,
+            };
+        }
+
+        let flight_time_range = self.flight_time_range(start_pos, goal_pos);
+
+        // Try arcs from the cheapest outwards until one clears the level
+        if let (FlightTimeChoice::CheapestClear, Some(range)) =
+            (flight_time_choice, flight_time_range)
+        {
+            for flight_time in self.flight_times_by_launch_speed(start_pos, goal_pos, range) {
+                let jump = self.jump_launch(start_pos, goal_pos, flight_time);
+
+                if self.allows_steering(&jump, STEERING_SPEED_TOLERANCE)
+                    && self
+                        .first_collision(start_pos, &jump, level, ignored_lines)
+                        .is_none()
+                {
+                    return JumpResult {
+                        reachable: true,
+                        feasible: true,
+                        launch_velocity: jump.launch_velocity,
+                        flight_time,
+                        hold_time: jump.hold_time,
+                        steering: jump.steering,
+                        flight_time_range,
+                        trajectory: self.sample_trajectory(start_pos, goal_pos, &jump),
+                        collision: None,
+                        line_of_sight_hit: None,
+                    };
+                }
+            }
+        }
+
+        // Every arc hit something, so report the cheapest one and what it hit
+        let (_, flight_time) = self.launch(start_pos, goal_pos, flight_time_choice);
+        let jump = self.jump_launch(start_pos, goal_pos, flight_time);
+
+        // Custom flight times outside the range go over the movement model's limits. With air control
+        // there can be flight times inside the range that launch too fast across to steer any further.
+        let reachable = flight_time_range.is_some_and(|(min_time, max_time)| {
+            let tolerance = max_time * FLIGHT_TIME_TOLERANCE;
+
+            (min_time - tolerance..=max_time + tolerance).contains(&flight_time)
+        }) && self.allows_steering(&jump, STEERING_SPEED_TOLERANCE);
+
+        let trajectory = self.sample_trajectory(start_pos, goal_pos, &jump);
+
+        // This is much cheaper than the exact check, so it goes first
+        let line_of_sight_hit = if flight_time_choice == FlightTimeChoice::CheapestClear {
+            None
+        } else {
+            level.raycast_ignoring(start_pos, goal_pos, ignored_lines)
+        };
+
+        let collision = if reachable && line_of_sight_hit.is_none() {
+            self.first_collision(start_pos, &jump, level, ignored_lines)
+        } else {
+            None
+        };
+
+        JumpResult {
+            reachable,
+            feasible: reachable && line_of_sight_hit.is_none() && collision.is_none(),
+            launch_velocity: jump.launch_velocity,
+            flight_time,
+            hold_time: jump.hold_time,
+            steering: jump.steering,
+            flight_time_range,
+            trajectory,
+            collision,
+            line_of_sight_hit,
+        }
+    }
+
+    /// Samples `sample_count` segments along the trajectory, ending exactly on the goal
+    pub fn sample_trajectory(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        jump: &JumpLaunch,
+    ) -> Vec<Vec2> {
+        let sample_count = self.profile.sample_count.max(1);
+        let timestep = jump.flight_time / sample_count as f32;
+        let pieces = self.trajectory_pieces(start_pos, jump);
+
+        let mut trajectory = Vec::with_capacity(sample_count + 1);
+        trajectory.push(start_pos);
+
+        for i in 1..sample_count {
+            let t = timestep * i as f32;
+            let piece = pieces
+                .iter()
+                .rfind(|piece| piece.start_time <= t)
+                .unwrap_or(&pieces[0]);
+
+            trajectory.push(piece.position_at(t - piece.start_time));
+        }
+
+        trajectory.push(goal_pos);
+
+        trajectory
+    }
+
+    /// The launch velocity and flight time of the chosen jump from `start_pos` to `goal_pos`.
+    /// The minimum time and maximum height choices fall back to the low energy jump when the goal is out of reach.
+    pub fn launch(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        flight_time_choice: FlightTimeChoice,
+    ) -> (Vec2, f32) {
+        let flight_time_range = self.flight_time_range(start_pos, goal_pos);
+        let t_low_energy = self.low_energy_flight_time(goal_pos - start_pos, flight_time_range);
+
+        let flight_time = match (flight_time_choice, flight_time_range) {
+            (FlightTimeChoice::MinimumTime, Some((min_time, _))) => min_time,
+            (FlightTimeChoice::MaximumHeight, Some((_, max_time))) => max_time,
+            (FlightTimeChoice::Custom(flight_time), _) => flight_time,
+            _ => t_low_energy,
+        };
+
+        (
+            self.launch_velocity(start_pos, goal_pos, flight_time),
+            flight_time,
+        )
+    }
+
+    /// Everything needed to follow the jump from `start_pos` that lands on `goal_pos` after exactly `flight_time`
+    fn jump_launch(&self, start_pos: Vec2, goal_pos: Vec2, flight_time: f32) -> JumpLaunch {
+        JumpLaunch {
+            launch_velocity: self.launch_velocity(start_pos, goal_pos, flight_time),
+            flight_time,
+            hold_time: self.hold_time(start_pos, goal_pos, flight_time),
+            steering: self.steering(start_pos, goal_pos, flight_time),
+        }
+    }
+
+    /// Evenly spread flight times across `flight_time_range`, plus the low energy one, ordered from the
+    /// lowest launch speed to the highest
+    fn flight_times_by_launch_speed(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        flight_time_range: (f32, f32),
+    ) -> Vec<f32> {
+        let (min_time, max_time) = flight_time_range;
+
+        // The squared launch speed is convex in the squared flight time, so spread the samples over that
+        let (min_time_squared, max_time_squared) = (min_time * min_time, max_time * max_time);
+        let low_energy_time =
+            self.low_energy_flight_time(goal_pos - start_pos, Some(flight_time_range));
+
+        let mut flight_times: Vec<f32> = (0..=FLIGHT_TIME_SEARCH_STEPS)
+            .map(|i| {
+                let fraction = i as f32 / FLIGHT_TIME_SEARCH_STEPS as f32;
+                (min_time_squared + (max_time_squared - min_time_squared) * fraction).sqrt()
+            })
+            .collect();
+        flight_times.push(low_energy_time);
+
+        let launch_speed_squared = |flight_time: f32| {
+            self.launch_velocity(start_pos, goal_pos, flight_time)
+                .length_squared()
+        };
+
+        flight_times.sort_by(|a, b| launch_speed_squared(*a).total_cmp(&launch_speed_squared(*b)));
+
+        flight_times
+    }
+
+    /// The flight time needing the least launch speed, or the nearest end of `flight_time_range` to it
+    fn low_energy_flight_time(&self, delta_p: Vec2, flight_time_range: Option<(f32, f32)>) -> f32 {
+        let acceleration = self.profile.gravity;
+
+        // The jump speed is fixed, so the slowest sideways launch is the cheapest
+        if let (MovementModel::VariableHeight { .. }, Some((_, max_time))) =
+            (self.profile.movement, flight_time_range)
+        {
+            return max_time;
+        }
+
+        let t_low_energy = (4.0 * delta_p.dot(delta_p) / acceleration.dot(acceleration))
+            .sqrt()
+            .sqrt();
+
+        match flight_time_range {
+            Some((min_time, max_time)) => t_low_energy.clamp(min_time, max_time),
+            None => t_low_energy,
+        }
+    }
+
+    /// The velocity to launch at so the jump lands on `goal_pos` after exactly `flight_time`
+    pub fn launch_velocity(&self, start_pos: Vec2, goal_pos: Vec2, flight_time: f32) -> Vec2 {
+        let delta_p = goal_pos - start_pos;
+        let up = -self.profile.gravity.normalize_or_zero();
+
+        let launch_velocity = match self.profile.movement {
+            // The jump speed is fixed, how long the jump is held makes up for the flight time
+            MovementModel::VariableHeight { jump_speed, .. } => {
+                up * jump_speed + up.perp() * delta_p.dot(up.perp()) / flight_time
+            }
+            _ => delta_p / flight_time - self.profile.gravity * flight_time / 2.0,
+        };
+
+        if self.profile.air_control.is_none() {
+            return launch_velocity;
+        }
+
+        // With air control the launch only goes as fast across as the movement model allows,
+        // and steering makes up the rest
+        let launch_speed_up = self.launch_speed_up(delta_p.dot(up), flight_time);
+        let max_speed_across = self.max_launch_speed_across(launch_speed_up);
+
+        up * launch_speed_up
+            + up.perp()
+                * (delta_p.dot(up.perp()) / flight_time).clamp(-max_speed_across, max_speed_across)
+    }
+
+    /// How fast the jump that comes back down to `rise` after `flight_time` is launched against gravity
+    fn launch_speed_up(&self, rise: f32, flight_time: f32) -> f32 {
+        match self.profile.movement {
+            MovementModel::VariableHeight { jump_speed, .. } => jump_speed,
+            _ => rise / flight_time + self.profile.gravity.length() * flight_time / 2.0,
+        }
+    }
+
+    /// How to steer so the jump lands on `goal_pos` after exactly `flight_time`.
+    /// This is `None` when the launch goes far enough across by itself, or the profile has no air control.
+    pub fn steering(
+        &self,
+        start_pos: Vec2,
+        goal_pos: Vec2,
+        flight_time: f32,
+    ) -> Option<AirSteering> {
+        let air_control = self.profile.air_control?;
+
+        if air_control.max_acceleration <= 0.0 {
+            return None;
+        }
+
+        let up = -self.profile.gravity.normalize_or_zero();
+        let across = (goal_pos - start_pos).dot(up.perp());
+        let launch_speed_up = self.launch_speed_up((goal_pos - start_pos).dot(up), flight_time);
+
+        let launch_distance = self.max_launch_speed_across(launch_speed_up) * flight_time;
+        if across.abs() <= launch_distance {
+            return None;
+        }
+
+        let shortfall = across.abs() - launch_distance;
+
+        // Speeding up for d and coasting for the rest of T goes a d (T - d/2) further than the launch alone
+        let duration = flight_time
+            - (flight_time * flight_time - 2.0 * shortfall / air_control.max_acceleration)
+                .max(0.0)
+                .sqrt();
+
+        Some(AirSteering {
+            acceleration: up.perp() * across.signum() * air_control.max_acceleration,
+            duration,
+        })
+    }
+
+    /// Whether the profile's limits allow following `jump`, give or take `tolerance`.
+    /// This checks jumps found with a different profile, like a graph built for a stronger character.
+    pub fn allows_launch(&self, jump: &JumpLaunch, tolerance: f32) -> bool {
+        let up = -self.profile.gravity.normalize_or_zero();
+        let launch_speed_up = jump.launch_velocity.dot(up);
+        let launch_speed_across = jump.launch_velocity.dot(up.perp()).abs();
+
+        let launch_allowed = match self.profile.movement {
+            MovementModel::Artillery => {
+                jump.launch_velocity.length() <= self.profile.max_launch_speed + tolerance
+            }
+            MovementModel::Platformer {
+                min_jump_speed,
+                max_jump_speed,
+                max_horizontal_speed,
+            } => {
+                (min_jump_speed - tolerance..=max_jump_speed + tolerance).contains(&launch_speed_up)
+                    && launch_speed_across <= max_horizontal_speed + tolerance
+            }
+            MovementModel::VariableHeight {
+                jump_speed,
+                max_horizontal_speed,
+                min_hold_time,
+                max_hold_time,
+                ..
+            } => {
+                (launch_speed_up - jump_speed).abs() <= tolerance
+                    && launch_speed_across <= max_horizontal_speed + tolerance
+                    && jump.hold_time.is_some_and(|hold_time| {
+                        (min_hold_time - tolerance..=max_hold_time + tolerance).contains(&hold_time)
+                    })
+            }
+        };
+
+        launch_allowed && self.allows_steering(jump, tolerance)
+    }
+
+    /// Whether the air control can steer `jump` the way it's meant to, give or take `tolerance`
+    fn allows_steering(&self, jump: &JumpLaunch, tolerance: f32) -> bool {
+        match (jump.steering, self.profile.air_control) {
+            (None, _) => true,
+            (Some(steering), Some(air_control)) => {
+                let up = -self.profile.gravity.normalize_or_zero();
+                let launch_speed_across = jump.launch_velocity.dot(up.perp()).abs();
+                let acceleration = steering.acceleration.length();
+                let steered_speed = launch_speed_across + acceleration * steering.duration;
+
+                acceleration <= air_control.max_acceleration + tolerance
+                    && steered_speed <= air_control.max_speed.max(launch_speed_across) + tolerance
+            }
+            (Some(_), None) => false,
+        }
+    }
+
+    /// How long to hold the jump so it lands on `goal_pos` after exactly `flight_time`.
+    /// This is `None` for movement models where the jump button doesn't change the jump.
+    pub fn hold_time(&self, start_pos: Vec2, goal_pos: Vec2, flight_time: f32) -> Option<f32> {
+        let MovementModel::VariableHeight {
+            jump_speed,
+            min_hold_time,
+            max_hold_time,
+            release_gravity_multiplier,
+            ..
+        } = self.profile.movement
+        else {
+            return None;
+        };
+
+        let gravity = self.profile.gravity.length();
+        let rise = (goal_pos - start_pos).dot(-self.profile.gravity.normalize_or_zero());
+
+        // Released after h, the height at T is v T - g T^2/2 - (m - 1) g (T - h)^2/2, so solve that for h.
+        // With no change in gravity the hold doesn't matter, so it's held until landing.
+        let multiplier_gain = (release_gravity_multiplier - 1.0) * gravity;
+        let falling_time = if multiplier_gain.abs() < f32::EPSILON {
+            0.0
+        } else {
+            let height_lost =
+                jump_speed * flight_time - gravity * flight_time * flight_time / 2.0 - rise;
+
+            (2.0 * height_lost / multiplier_gain).max(0.0).sqrt()
+        };
+
+        Some((flight_time - falling_time).clamp(min_hold_time, max_hold_time.max(min_hold_time)))
+    }
+
+    /// The shortest and longest flight times from `start_pos` to `goal_pos` that the movement model allows,
+    /// or `None` when the goal is out of reach
+    pub fn flight_time_range(&self, start_pos: Vec2, goal_pos: Vec2) -> Option<(f32, f32)> {
+        let delta_p = goal_pos - start_pos;
+
+        if delta_p.length_squared() < f32::EPSILON {
+            return None;
+        }
+
+        match self.profile.air_control {
+            Some(air_control) => self.steered_flight_time_range(delta_p, air_control),
+            None => self.launch_flight_time_range(delta_p),
+        }
+    }
+
+    /// The flight times that reach `delta_p` by the launch alone
+    fn launch_flight_time_range(&self, delta_p: Vec2) -> Option<(f32, f32)> {
+        match self.profile.movement {
+            MovementModel::Artillery => self.artillery_flight_time_range(delta_p),
+            MovementModel::Platformer {
+                min_jump_speed,
+                max_jump_speed,
+                max_horizontal_speed,
+            } => self.platformer_flight_time_range(
+                delta_p,
+                min_jump_speed,
+                max_jump_speed,
+                max_horizontal_speed,
+            ),
+            MovementModel::VariableHeight {
+                jump_speed,
+                max_horizontal_speed,
+                min_hold_time,
+                max_hold_time,
+                release_gravity_multiplier,
+            } => self.variable_height_flight_time_range(
+                delta_p,
+                jump_speed,
+                max_horizontal_speed,
+                min_hold_time,
+                max_hold_time,
+                release_gravity_multiplier,
+            ),
+        }
+    }
+
+    fn artillery_flight_time_range(&self, delta_p: Vec2) -> Option<(f32, f32)> {
+        let acceleration = self.profile.gravity;
+        let v_max = self.profile.max_launch_speed;
+
+        // Without gravity nothing comes back down, and the flight times below would divide by zero
+        if acceleration.length_squared() < f32::EPSILON {
+            return None;
+        }
+
+        // The squared flight times are the roots of |g|^2/4 T^2 - b1 T + |d|^2 = 0
+        let b1 = delta_p.dot(acceleration) + v_max * v_max;
+        let discriminant = b1 * b1 - acceleration.dot(acceleration) * delta_p.dot(delta_p);
+
+        // The goal is outside the envelope of every trajectory the maximum launch speed allows
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let max_time_squared = 2.0 * (b1 + discriminant.sqrt()) / acceleration.dot(acceleration);
+        // The product of the roots is 4|d|^2/|g|^2, which avoids the cancellation in b1 - sqrt(discriminant)
+        let min_time_squared =
+            4.0 * delta_p.dot(delta_p) / acceleration.dot(acceleration) / max_time_squared;
+
+        Some((min_time_squared.sqrt(), max_time_squared.sqrt()))
+    }
+
+    fn platformer_flight_time_range(
+        &self,
+        delta_p: Vec2,
+        min_jump_speed: f32,
+        max_jump_speed: f32,
+        max_horizontal_speed: f32,
+    ) -> Option<(f32, f32)> {
+        let gravity = self.profile.gravity.length();
+
+        if gravity < f32::EPSILON {
+            return None;
+        }
+
+        // Split the jump into the part against gravity and the part across it
+        let up = -self.profile.gravity / gravity;
+        let rise = delta_p.dot(up);
+        let across = delta_p.dot(up.perp()).abs();
+
+        // Even the strongest jump peaks below the goal
+        if max_jump_speed * max_jump_speed < 2.0 * gravity * rise {
+            return None;
+        }
+
+        if across > 0.0 && max_horizontal_speed <= 0.0 {
+            return None;
+        }
+
+        // The time a jump at `jump_speed` takes to come back down to the goal's height
+        let descending_time = |jump_speed: f32| {
+            (jump_speed
+                + (jump_speed * jump_speed - 2.0 * gravity * rise)
+                    .max(0.0)
+                    .sqrt())
+                / gravity
+        };
+
+        // Past the peak the jump speed needed grows with the flight time, so the jump speed limits bound
+        // it from both sides. Going sideways any faster than the cap bounds it from below too.
+        let peak_time = (2.0 * rise / gravity).max(0.0).sqrt();
+        let horizontal_time = if across > 0.0 {
+            across / max_horizontal_speed
+        } else {
+            0.0
+        };
+
+        let min_time = peak_time
+            .max(descending_time(min_jump_speed))
+            .max(horizontal_time);
+        let max_time = descending_time(max_jump_speed);
+
+        (min_time <= max_time).then_some((min_time, max_time))
+    }
+
+    fn variable_height_flight_time_range(
+        &self,
+        delta_p: Vec2,
+        jump_speed: f32,
+        max_horizontal_speed: f32,
+        min_hold_time: f32,
+        max_hold_time: f32,
+        release_gravity_multiplier: f32,
+    ) -> Option<(f32, f32)> {
+        let gravity = self.profile.gravity.length();
+
+        if gravity < f32::EPSILON || release_gravity_multiplier <= 0.0 {
+            return None;
+        }
+
+        // Split the jump into the part against gravity and the part across it
+        let up = -self.profile.gravity / gravity;
+        let rise = delta_p.dot(up);
+        let across = delta_p.dot(up.perp()).abs();
+
+        if across > 0.0 && max_horizontal_speed <= 0.0 {
+            return None;
+        }
+
+        let release_gravity = gravity * release_gravity_multiplier;
+        let held_discriminant = jump_speed * jump_speed - 2.0 * gravity * rise;
+
+        // Holding for longer always moves the peak the same way, up when gravity gets stronger on release
+        // and down when it gets weaker, so the holds that peak above the goal are a range too
+        let mut shortest_hold = min_hold_time.max(0.0);
+        let mut longest_hold = max_hold_time;
+
+        if (release_gravity_multiplier - 1.0).abs() < f32::EPSILON {
+            if held_discriminant < 0.0 {
+                return None;
+            }
+        } else {
+            // Solved for the hold that peaks exactly at the goal's height
+            let peak_discriminant =
+                release_gravity_multiplier * held_discriminant / (release_gravity_multiplier - 1.0);
+
+            if peak_discriminant >= 0.0 {
+                let peak_hold = (jump_speed - peak_discriminant.sqrt()) / gravity;
+
+                if release_gravity_multiplier > 1.0 {
+                    shortest_hold = shortest_hold.max(peak_hold);
+                } else {
+                    longest_hold = longest_hold.min(peak_hold);
+                }
+            } else if release_gravity_multiplier > 1.0 {
+                return None;
+            }
+        }
+
+        if shortest_hold > longest_hold {
+            return None;
+        }
+
+        // The time a jump released after `hold_time` takes to come back down to the goal's height
+        let landing_time = |hold_time: f32| {
+            if held_discriminant >= 0.0 {
+                let held_landing_time = (jump_speed + held_discriminant.sqrt()) / gravity;
+
+                if held_landing_time <= hold_time {
+                    return held_landing_time;
+                }
+            }
+
+            let release_height = jump_speed * hold_time - gravity * hold_time * hold_time / 2.0;
+            let release_speed = jump_speed - gravity * hold_time;
+            let discriminant =
+                release_speed * release_speed - 2.0 * release_gravity * (rise - release_height);
+
+            hold_time + (release_speed + discriminant.max(0.0).sqrt()) / release_gravity
+        };
+
+        // The landing time follows the hold, so the ends of the hold range give the ends of the flight time range
+        let shortest_hold_landing = landing_time(shortest_hold);
+        let longest_hold_landing = landing_time(longest_hold);
+        let horizontal_time = if across > 0.0 {
+            across / max_horizontal_speed
+        } else {
+            0.0
+        };
+
+        let min_time = shortest_hold_landing
+            .min(longest_hold_landing)
+            .max(horizontal_time);
+        let max_time = shortest_hold_landing.max(longest_hold_landing);
+
+        (min_time <= max_time).then_some((min_time, max_time))
+    }
+
+    /// The flight times that reach `delta_p` when the launch can be followed by steering.
+    /// Steering only ever adds to how far across the jump goes, so this always covers the launch alone's range.
+    fn steered_flight_time_range(
+        &self,
+        delta_p: Vec2,
+        air_control: AirControl,
+    ) -> Option<(f32, f32)> {
+        // Right at the edge of the launch's reach only a single flight time works, which the search steps over
+        let launch_range = self.launch_flight_time_range(delta_p);
+        let searched_range = self.searched_steered_flight_time_range(delta_p, air_control);
+
+        match (launch_range, searched_range) {
+            (Some((launch_min, launch_max)), Some((searched_min, searched_max))) => {
+                Some((launch_min.min(searched_min), launch_max.max(searched_max)))
+            }
+            (range, None) | (None, range) => range,
+        }
+    }
+
+    /// Steering doesn't change the height of the jump, so the flight times that reach the goal's height are
+    /// searched for the ones that also get far enough across. The search tries evenly spread flight times,
+    /// so a goal only reachable in a gap narrower than their spacing is missed.
+    fn searched_steered_flight_time_range(
+        &self,
+        delta_p: Vec2,
+        air_control: AirControl,
+    ) -> Option<(f32, f32)> {
+        let up = -self.profile.gravity.normalize_or_zero();
+        let rise = delta_p.dot(up);
+        let across = delta_p.dot(up.perp()).abs();
+
+        let (min_height_time, max_height_time) = self.launch_flight_time_range(up * rise)?;
+
+        let reaches = |flight_time: f32| {
+            flight_time > 0.0 && across <= self.max_steered_distance(rise, flight_time, air_control)
+        };
+
+        let flight_times: Vec<f32> = (0..=STEERED_FLIGHT_TIME_SAMPLES)
+            .map(|i| {
+                let fraction = i as f32 / STEERED_FLIGHT_TIME_SAMPLES as f32;
+                min_height_time + (max_height_time - min_height_time) * fraction
+            })
+            .collect();
+
+        let first = flight_times.iter().position(|&t| reaches(t))?;
+        let last = flight_times.iter().rposition(|&t| reaches(t))?;
+
+        let min_time = match first {
+            0 => flight_times[0],
+            _ => bisect_boundary(flight_times[first - 1], flight_times[first], reaches),
+        };
+        let max_time = match last {
+            STEERED_FLIGHT_TIME_SAMPLES => flight_times[last],
+            _ => bisect_boundary(flight_times[last + 1], flight_times[last], reaches),
+        };
+
+        Some((min_time, max_time))
+    }
+
+    /// The furthest across gravity a jump that comes back down to `rise` after `flight_time` can go,
+    /// launching as fast across as the movement model allows and then steering as hard as it can
+    fn max_steered_distance(&self, rise: f32, flight_time: f32, air_control: AirControl) -> f32 {
+        let launch_speed = self.max_launch_speed_across(self.launch_speed_up(rise, flight_time));
+
+        let acceleration = air_control.max_acceleration;
+        if launch_speed >= air_control.max_speed || acceleration <= 0.0 {
+            return launch_speed * flight_time;
+        }
+
+        // Speed up until reaching the steering speed limit, then coast
+        let steering_time =
+            ((air_control.max_speed - launch_speed) / acceleration).min(flight_time);
+
+        launch_speed * flight_time
+            + acceleration * steering_time * (flight_time - steering_time / 2.0)
+    }
+
+    /// The fastest the movement model can launch across gravity, alongside launching `launch_speed_up` against it
+    fn max_launch_speed_across(&self, launch_speed_up: f32) -> f32 {
+        match self.profile.movement {
+            MovementModel::Artillery => {
+                let max_launch_speed = self.profile.max_launch_speed;
+
+                (max_launch_speed * max_launch_speed - launch_speed_up * launch_speed_up)
+                    .max(0.0)
+                    .sqrt()
+            }
+            MovementModel::Platformer {
+                max_horizontal_speed,
+                ..
+            }
+            | MovementModel::VariableHeight {
+                max_horizontal_speed,
+                ..
+            } => max_horizontal_speed.max(0.0),
+        }
+    }
+
+    /// Whether the goal can be reached with any flight time the movement model allows, ignoring the level.
+    /// This is cheap, so it's worth checking before any collision work.
+    pub fn in_reach(&self, start_pos: Vec2, goal_pos: Vec2) -> bool {
+        self.flight_time_range(start_pos, goal_pos).is_some()
+    }
+
+    /// Finds the first level line that the character would hit while following the trajectory.
+    /// The character is swept as a circle along the exact parabolas, so corners can't slip through.
+    pub fn first_collision(
+        &self,
+        start_pos: Vec2,
+        jump: &JumpLaunch,
+        level: &Level,
+        ignored_lines: &[PolygonLine],
+    ) -> Option<JumpCollision> {
+        // The pieces follow each other, so the first one to hit anything has the first collision
+        self.trajectory_pieces(start_pos, jump)
+            .iter()
+            .find_map(|piece| self.first_piece_collision(piece, level, ignored_lines))
+    }
+
+    fn first_piece_collision(
+        &self,
+        piece: &TrajectoryPiece,
+        level: &Level,
+        ignored_lines: &[PolygonLine],
+    ) -> Option<JumpCollision> {
+        let mut first_collision: Option<JumpCollision> = None;
+
+        let nearby_lines = level.lines_near_trajectory(
+            piece.start_pos,
+            piece.velocity,
+            piece.acceleration,
+            self.profile.character_radius,
+            piece.duration,
+        );
+
+        for line in nearby_lines {
+            if ignored_lines.contains(&line) {
+                continue;
+            }
+
+            let (&start, &end) = level
+                .get_line(line.polygon_index, line.line_index)
+                .expect("The segment grid only holds lines of this level");
+
+            let contact_time = swept_circle_line_contact(
+                piece.start_pos,
+                piece.velocity,
+                piece.acceleration,
+                self.profile.character_radius,
+                piece.duration,
+                start,
+                end,
+            );
+
+            let Some(piece_time) = contact_time else {
+                continue;
+            };
+
+            let time = piece.start_time + piece_time;
+
+            if first_collision
+                .as_ref()
+                .is_none_or(|collision| time < collision.time)
+            {
+                first_collision = Some(JumpCollision {
+                    line,
+                    point: closest_point_on_line(start, end, piece.position_at(piece_time)),
+                    time,
+                });
+            }
+        }
+
+        first_collision
+    }
+
+    /// The trajectory as quadratic Bezier curves, given as their start, control and end points.
+    /// A parabola is exactly a quadratic Bezier curve with its control point where the launch tangent
+    /// and the landing tangent meet, halfway through the flight along the launch velocity.
+    pub fn trajectory_beziers(&self, start_pos: Vec2, jump: &JumpLaunch) -> Vec<[Vec2; 3]> {
+        self.trajectory_pieces(start_pos, jump)
+            .iter()
+            .map(|piece| {
+                [
+                    piece.start_pos,
+                    piece.start_pos + piece.velocity * piece.duration / 2.0,
+                    piece.position_at(piece.duration),
+                ]
+            })
+            .collect()
+    }
+
+    /// Splits the trajectory into the parabolas it's made of. A new one starts when the jump is released,
+    /// which makes gravity stronger, and when the character stops steering.
+    fn trajectory_pieces(&self, start_pos: Vec2, jump: &JumpLaunch) -> Vec<TrajectoryPiece> {
+        let flight_time = jump.flight_time;
+        let release_time = jump
+            .hold_time
+            .map_or(flight_time, |hold_time| hold_time.clamp(0.0, flight_time));
+        let steering_time = jump
+            .steering
+            .map_or(0.0, |steering| steering.duration.clamp(0.0, flight_time));
+        let steering_acceleration = jump
+            .steering
+            .map_or(Vec2::ZERO, |steering| steering.acceleration);
+
+        let release_gravity_multiplier = match self.profile.movement {
+            MovementModel::VariableHeight {
+                release_gravity_multiplier,
+                ..
+            } => release_gravity_multiplier,
+            _ => 1.0,
+        };
+
+        let mut piece_end_times = [release_time, steering_time, flight_time];
+        piece_end_times.sort_by(f32::total_cmp);
+
+        let mut pieces: Vec<TrajectoryPiece> = Vec::with_capacity(piece_end_times.len());
+        let mut piece = TrajectoryPiece {
+            start_time: 0.0,
+            start_pos,
+            velocity: jump.launch_velocity,
+            acceleration: self.profile.gravity,
+            duration: 0.0,
+        };
+
+        for end_time in piece_end_times {
+            if end_time <= piece.start_time {
+                continue;
+            }
+
+            if let Some(previous) = pieces.last() {
+                piece.start_pos = previous.position_at(previous.duration);
+                piece.velocity = previous.velocity + previous.acceleration * previous.duration;
+            }
+
+            let gravity_multiplier = if piece.start_time < release_time {
+                1.0
+            } else {
+                release_gravity_multiplier
+            };
+            let steering = if piece.start_time < steering_time {
+                steering_acceleration
+            } else {
+                Vec2::ZERO
+            };
+
+            piece.acceleration = self.profile.gravity * gravity_multiplier + steering;
+            piece.duration = end_time - piece.start_time;
+            pieces.push(piece);
+
+            piece.start_time = end_time;
+        }
+
+        // A jump with no flight time still starts somewhere
+        if pieces.is_empty() {
+            pieces.push(piece);
+        }
+
+        pieces
+    }
+}
+
+/// One parabola of a trajectory
+#[derive(Debug, Clone, Copy)]
+struct TrajectoryPiece {
+    /// How far into the jump the piece starts
+    start_time: f32,
+    start_pos: Vec2,
+    velocity: Vec2,
+    acceleration: Vec2,
+    duration: f32,
+}
+
+impl TrajectoryPiece {
+    /// The position `time` after the start of this piece
+    fn position_at(&self, time: f32) -> Vec2 {
+        self.start_pos + self.velocity * time + self.acceleration * time * time / 2.0
+    }
+}
+
+/// Narrows down the flight time where `reaches` stops being true, between one that doesn't reach and one
+/// that does. The returned flight time always reaches.
+fn bisect_boundary(mut outside: f32, mut inside: f32, reaches: impl Fn(f32) -> bool) -> f32 {
+    for _ in 0..STEERED_FLIGHT_TIME_BISECTIONS {
+        let middle = (outside + inside) / 2.0;
+
+        if reaches(middle) {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn air_control() -> AirControl {
+        AirControl {
+            max_acceleration: 0.1,
+            max_speed: 4.0,
+        }
+    }
+
+    fn platformer() -> MovementModel {
+        MovementModel::Platformer {
+            min_jump_speed: 2.0,
+            max_jump_speed: 8.0,
+            max_horizontal_speed: 3.0,
+        }
+    }
+
+    fn variable_height() -> MovementModel {
+        MovementModel::VariableHeight {
+            jump_speed: 7.0,
+            max_horizontal_speed: 3.0,
+            min_hold_time: 2.0,
+            max_hold_time: 12.0,
+            release_gravity_multiplier: 2.5,
+        }
+    }
+
+    fn profile_with(movement: MovementModel, air_control: Option<AirControl>) -> JumpProfile {
+        JumpProfile {
+            movement,
+            air_control,
+            ..JumpProfile::default()
+        }
+    }
+
+    /// Where following `jump` from `start_pos` really ends up, and how fast it's going there
+    fn simulate_landing(solver: &JumpSolver, start_pos: Vec2, jump: &JumpLaunch) -> (Vec2, Vec2) {
+        let pieces = solver.trajectory_pieces(start_pos, jump);
+        let last = pieces.last().unwrap();
+
+        (
+            last.position_at(last.duration),
+            last.velocity + last.acceleration * last.duration,
+        )
+    }
+
+    /// Re-simulates jumps to a grid of goals around the origin, across each goal's whole flight time range
+    /// and with the low energy flight time, checking they land on the goal and passing each launch to `check_limits`
+    fn check_launches_land_on_goals(
+        solver: &JumpSolver,
+        check_limits: impl Fn(Vec2, &JumpLaunch, Vec2),
+    ) -> usize {
+        let start_pos = Vec2::ZERO;
+        let mut goals_in_reach = 0;
+
+        for x in -20..=20 {
+            for y in -20..=20 {
+                let goal_pos = Vec2::new(x as f32, y as f32) * 16.0;
+                let Some((min_time, max_time)) = solver.flight_time_range(start_pos, goal_pos)
+                else {
+                    continue;
+                };
+                goals_in_reach += 1;
+
+                let low_energy_time =
+                    solver.low_energy_flight_time(goal_pos - start_pos, Some((min_time, max_time)));
+                let flight_times = (0..=4)
+                    .map(|i| min_time + (max_time - min_time) * i as f32 / 4.0)
+                    .chain([low_energy_time]);
+
+                for flight_time in flight_times {
+                    let jump = solver.jump_launch(start_pos, goal_pos, flight_time);
+                    let (landing_pos, landing_velocity) =
+                        simulate_landing(solver, start_pos, &jump);
+
+                    assert!(
+                        landing_pos.distance(goal_pos) < 1e-3,
+                        "aimed at {} after {} but landed on {}",
+                        goal_pos,
+                        flight_time,
+                        landing_pos
+                    );
+                    check_limits(goal_pos, &jump, landing_velocity);
+                }
+            }
+        }
+
+        goals_in_reach
+    }
+
+    #[test]
+    fn artillery_launches_land_on_the_goal_within_the_launch_speed() {
+        let profile = JumpProfile::default();
+        let solver = JumpSolver::new(&profile);
+
+        let goals_in_reach = check_launches_land_on_goals(&solver, |goal_pos, jump, _| {
+            assert!(
+                jump.launch_velocity.length() <= profile.max_launch_speed * (1.0 + 1e-4),
+                "launched at {} to reach {}",
+                jump.launch_velocity.length(),
+                goal_pos
+            );
+        });
+        assert!(goals_in_reach > 0);
+    }
+
+    #[test]
+    fn artillery_reach_ends_at_the_maximum_range() {
+        let solver = JumpSolver::new(&JumpProfile::default());
+
+        // v^2 / g on the flat, and v^2 / 2g straight up
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(127.9, 0.0)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(128.1, 0.0)));
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(0.0, 63.9)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(0.0, 64.1)));
+    }
+
+    #[test]
+    fn platformer_launches_land_on_the_goal_within_the_jump_and_horizontal_speeds() {
+        let solver = JumpSolver::new(&profile_with(platformer(), None));
+
+        let goals_in_reach =
+            check_launches_land_on_goals(&solver, |goal_pos, jump, landing_velocity| {
+                let jump_speed = jump.launch_velocity.y;
+
+                assert!(
+                    (2.0 - 1e-4..=8.0 + 1e-4).contains(&jump_speed),
+                    "jumped at {} to reach {}",
+                    jump_speed,
+                    goal_pos
+                );
+                assert!(
+                    jump.launch_velocity.x.abs() <= 3.0 + 1e-4,
+                    "launched across at {} to reach {}",
+                    jump.launch_velocity.x,
+                    goal_pos
+                );
+                assert!(
+                    landing_velocity.y <= 1e-4,
+                    "landed on {} on the way up",
+                    goal_pos
+                );
+            });
+        assert!(goals_in_reach > 0);
+    }
+
+    #[test]
+    fn platformer_reach_ends_at_the_jump_and_horizontal_speed_limits() {
+        let solver = JumpSolver::new(&profile_with(platformer(), None));
+
+        // The strongest jump peaks at 8^2 / 2g
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(10.0, 63.9)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(10.0, 64.1)));
+
+        // The strongest jump is back down after 2 * 8 / g, covering 3 * 32 across
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(95.9, 0.0)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(96.1, 0.0)));
+    }
+
+    #[test]
+    fn variable_height_launches_land_on_the_goal_within_the_hold_limits() {
+        let solver = JumpSolver::new(&profile_with(variable_height(), None));
+
+        let goals_in_reach =
+            check_launches_land_on_goals(&solver, |goal_pos, jump, landing_velocity| {
+                let hold_time = jump.hold_time.expect("variable height jumps are held");
+
+                assert!(
+                    (2.0 - 1e-4..=12.0 + 1e-4).contains(&hold_time),
+                    "held for {} to reach {}",
+                    hold_time,
+                    goal_pos
+                );
+                assert!(
+                    (jump.launch_velocity.y - 7.0).abs() < 1e-4,
+                    "jumped at {} to reach {}",
+                    jump.launch_velocity.y,
+                    goal_pos
+                );
+                assert!(
+                    jump.launch_velocity.x.abs() <= 3.0 + 1e-4,
+                    "launched across at {} to reach {}",
+                    jump.launch_velocity.x,
+                    goal_pos
+                );
+                assert!(
+                    landing_velocity.y <= 1e-4,
+                    "landed on {} on the way up",
+                    goal_pos
+                );
+            });
+        assert!(goals_in_reach > 0);
+    }
+
+    #[test]
+    fn variable_height_reach_ends_at_the_longest_hold() {
+        let solver = JumpSolver::new(&profile_with(variable_height(), None));
+
+        // Released at 12 it's 48 up and still rising at 1, which stronger gravity stops 0.4 higher
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(10.0, 48.3)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(10.0, 48.5)));
+
+        // The longest hold lands after 12 + (1 + sqrt(1 + 2 * 1.25 * 48)) / 1.25 = 21.6, covering 3 * 21.6 across
+        assert!(solver.in_reach(Vec2::ZERO, Vec2::new(64.7, 0.0)));
+        assert!(!solver.in_reach(Vec2::ZERO, Vec2::new(64.9, 0.0)));
+    }
+
+    #[test]
+    fn steering_reaches_goals_the_launch_alone_falls_short_of() {
+        let start_pos = Vec2::ZERO;
+
+        // Each goal is past the furthest the launch alone goes on the flat. The artillery model's furthest
+        // launch already goes faster across than the usual steering speed limit, so it gets a higher one.
+        for (movement, max_speed, goal_pos) in [
+            (MovementModel::Artillery, 8.0, Vec2::new(140.0, 0.0)),
+            (platformer(), 4.0, Vec2::new(110.0, 0.0)),
+            (variable_height(), 4.0, Vec2::new(-80.0, 0.0)),
+        ] {
+            let air_control = AirControl {
+                max_speed,
+                ..air_control()
+            };
+            let without = JumpSolver::new(&profile_with(movement, None));
+            let with = JumpSolver::new(&profile_with(movement, Some(air_control)));
+
+            assert!(!without.in_reach(start_pos, goal_pos));
+            let (min_time, max_time) = with
+                .flight_time_range(start_pos, goal_pos)
+                .expect("steering should bring the goal in reach");
+
+            for i in 0..=4 {
+                let flight_time = min_time + (max_time - min_time) * i as f32 / 4.0;
+                let jump = with.jump_launch(start_pos, goal_pos, flight_time);
+                let steering = jump.steering.expect("the launch alone falls short");
+
+                let (landing_pos, _) = simulate_landing(&with, start_pos, &jump);
+                assert!(
+                    landing_pos.distance(goal_pos) < 1e-3,
+                    "{:?} aimed at {} after {} but landed on {}",
+                    movement,
+                    goal_pos,
+                    flight_time,
+                    landing_pos
+                );
+
+                assert!(steering.acceleration.length() <= air_control.max_acceleration + 1e-6);
+                for piece in with.trajectory_pieces(start_pos, &jump) {
+                    let end_velocity = piece.velocity + piece.acceleration * piece.duration;
+
+                    assert!(
+                        end_velocity.x.abs() <= air_control.max_speed + 1e-3,
+                        "{:?} steered up to {} across",
+                        movement,
+                        end_velocity.x
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_in_reach_without_gravity() {
+        let level = Level::from_json(crate::level::DEFAULT_LEVEL_DATA, 32.0).unwrap();
+
+        for movement in [MovementModel::Artillery, platformer(), variable_height()] {
+            let profile = JumpProfile {
+                gravity: Vec2::ZERO,
+                ..profile_with(movement, None)
+            };
+            let solver = JumpSolver::new(&profile);
+            let (start_pos, goal_pos) = (Vec2::ZERO, Vec2::new(64.0, 0.0));
+
+            assert_eq!(solver.flight_time_range(start_pos, goal_pos), None);
+
+            let jump = solver.solve(start_pos, goal_pos, &level, &[]);
+            assert!(!jump.reachable && !jump.feasible);
+
+            // The graph builder checks every pair, so it mustn't panic either
+            let mut pathfinding = crate::pathfinding::Pathfinding::default();
+            crate::pathfinding::init_pathfinding_graph(&level, &profile, &mut pathfinding);
+            assert!(pathfinding
+                .nodes
+                .iter()
+                .all(|node| node.jumpable_connections.is_empty()));
+        }
+    }
+
+    #[test]
+    fn air_control_keeps_goals_at_the_edge_of_the_launch_reach() {
+        let solver = JumpSolver::new(&profile_with(MovementModel::Artillery, Some(air_control())));
+
+        // The furthest the default launch speed throws on the flat, with a single flight time
+        let start_pos = Vec2::new(-16.0, 208.0);
+        let goal_pos = Vec2::new(112.0, 208.0);
+
+        assert!(solver.in_reach(start_pos, goal_pos));
+    }
+
+    #[test]
+    fn air_control_only_adds_goals_in_reach() {
+        let start_pos = Vec2::ZERO;
+
+        for movement in [MovementModel::Artillery, platformer(), variable_height()] {
+            let without = JumpSolver::new(&profile_with(movement, None));
+            let with = JumpSolver::new(&profile_with(movement, Some(air_control())));
+
+            for x in -20..=20 {
+                for y in -20..=20 {
+                    let goal_pos = Vec2::new(x as f32, y as f32) * 16.0;
+
+                    if without.in_reach(start_pos, goal_pos) {
+                        assert!(
+                            with.in_reach(start_pos, goal_pos),
+                            "{:?} lost {} with air control",
+                            movement,
+                            goal_pos
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,55 @@
-use bevy::{ecs::system::Resource, math::Vec2, render::color::Color};
+use std::{fmt, path::Path};
+
+#[cfg(feature = "render")]
+use bevy::ecs::system::Resource;
+use glam::Vec2;
 use rand::Rng;
+use serde::Serialize;
 
-use crate::utils::line_intersect;
+use crate::{
+    segment_grid::SegmentGrid,
+    utils::{line_intersect, line_intersect_fractions},
+};
 
 pub struct Polygon {
     pub points: Vec<Vec2>,
-    pub color: Color,
+    /// The RGB color the polygon is drawn with
+    pub color: [f32; 3],
     pub is_container: bool,
 }
 
-#[derive(Resource)]
+#[cfg_attr(feature = "render", derive(Resource))]
 pub struct Level {
     pub polygons: Vec<Polygon>,
     pub grid_size: f32,
     pub size: Vec2,
     pub half_size: Vec2,
+    /// Broad phase for the collision queries, built once when the level is loaded
+    segment_grid: SegmentGrid,
 }
 
 impl Level {
+    /// Builds a level from the JSON tile grid in `level_data`
+    pub fn from_json(level_data: &[u8], grid_size: f32) -> Result<Level, LevelError> {
+        let (polygons, size, half_size) = generate_level_polygons(level_data, grid_size)?;
+        let segment_grid = SegmentGrid::new(&polygons, grid_size);
+
+        Ok(Level {
+            segment_grid,
+            polygons,
+            grid_size,
+            size,
+            half_size,
+        })
+    }
+
+    /// Reads a JSON tile grid from disk and builds a level from it
+    pub fn load(path: impl AsRef<Path>, grid_size: f32) -> Result<Level, LevelError> {
+        let level_data = std::fs::read(path)?;
+
+        Level::from_json(&level_data, grid_size)
+    }
+
     pub fn get_polygon(&self, index: usize) -> Option<&Polygon> {
         self.polygons.get(index)
     }
@@ -31,31 +63,229 @@ impl Level {
         Some((start, end))
     }
 
+    /// The normal of a line, pointing out of the solid side of the polygon
+    pub fn get_line_normal(&self, polygon_index: usize, line_index: usize) -> Option<Vec2> {
+        let (start, end) = self.get_line(polygon_index, line_index)?;
+
+        let line = *end - *start;
+
+        Some(Vec2::new(-line.y, line.x).normalize_or_zero())
+    }
+
+    /// Finds the closest level line hit by the ray from `start` to `end`
+    pub fn raycast(&self, start: Vec2, end: Vec2) -> Option<RaycastHit> {
+        self.raycast_ignoring(start, end, &[])
+    }
+
+    /// Finds the closest level line hit by the ray from `start` to `end`, passing straight through `ignored_lines`
+    pub fn raycast_ignoring(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        ignored_lines: &[PolygonLine],
+    ) -> Option<RaycastHit> {
+        let mut closest_hit: Option<RaycastHit> = None;
+
+        for line in self.segment_grid.lines_near_segment(start, end, 0.0) {
+            if ignored_lines.contains(&line) {
+                continue;
+            }
+
+            let (&line_start, &line_end) = self
+                .get_line(line.polygon_index, line.line_index)
+                .expect("The segment grid only holds lines of this level");
+
+            let Some((fraction, _)) = line_intersect_fractions(start, end, line_start, line_end)
+            else {
+                continue;
+            };
+
+            if closest_hit
+                .as_ref()
+                .is_none_or(|closest_hit| fraction < closest_hit.fraction)
+            {
+                let line_dir = line_end - line_start;
+
+                closest_hit = Some(RaycastHit {
+                    line,
+                    point: start + (end - start) * fraction,
+                    normal: Vec2::new(-line_dir.y, line_dir.x).normalize_or_zero(),
+                    fraction,
+                });
+            }
+        }
+
+        closest_hit
+    }
+
+    /// Every line that could be within `radius` of the segment from `start` to `end`, without testing them exactly
+    pub fn lines_near_segment(&self, start: Vec2, end: Vec2, radius: f32) -> Vec<PolygonLine> {
+        self.segment_grid.lines_near_segment(start, end, radius)
+    }
+
+    /// Every line that could be within `radius` of the parabola `start_pos + velocity * t + acceleration * t^2 / 2`
+    /// for `t` in `[0, max_t]`, without testing them exactly
+    pub fn lines_near_trajectory(
+        &self,
+        start_pos: Vec2,
+        velocity: Vec2,
+        acceleration: Vec2,
+        radius: f32,
+        max_t: f32,
+    ) -> Vec<PolygonLine> {
+        self.segment_grid
+            .lines_near_trajectory(start_pos, velocity, acceleration, radius, max_t)
+    }
+
+    /// Whether nothing in the level blocks the line from `start` to `end`
     pub fn line_of_sight_check(&self, start: Vec2, end: Vec2) -> bool {
-        for polygon in &self.polygons {
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
+        self.raycast(start, end).is_none()
+    }
+}
 
-                let intersection = line_intersect(start, end, start, end);
+/// Where a ray first hits the level
+#[derive(Debug, Clone, Serialize)]
+pub struct RaycastHit {
+    pub line: PolygonLine,
+    pub point: Vec2,
+    /// The normal of the line that was hit, pointing out of the solid side of the polygon
+    pub normal: Vec2,
+    /// How far along the ray the hit is, from 0 at the start to 1 at the end
+    pub fraction: f32,
+}
 
-                if intersection.is_some() {
-                    return false;
-                }
+/// Everything that can go wrong while turning a level file into polygons
+#[derive(Debug)]
+pub enum LevelError {
+    /// The level file could not be read
+    Io(std::io::Error),
+    /// The level file is not valid UTF-8
+    InvalidUtf8(std::str::Utf8Error),
+    /// The level file is not a JSON array of rows of tile ids
+    InvalidJson(serde_json::Error),
+    /// The level has no rows, or its first row has no tiles
+    EmptyGrid,
+    /// A row has a different number of tiles than the first row
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A tile id that doesn't correspond to any tile shape
+    UnknownTile {
+        row: usize,
+        column: usize,
+        tile: usize,
+    },
+    /// The outline of a polygon runs into a dead end instead of closing on itself
+    UnclosedOutline { row: usize, column: usize },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "could not read level file: {}", err),
+            LevelError::InvalidUtf8(err) => write!(f, "level file is not valid UTF-8: {}", err),
+            LevelError::InvalidJson(err) => {
+                write!(f, "level file is not a grid of tile ids: {}", err)
+            }
+            LevelError::EmptyGrid => write!(f, "level grid is empty"),
+            LevelError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} tiles, but the first row has {}",
+                row, found, expected
+            ),
+            LevelError::UnknownTile { row, column, tile } => {
+                write!(
+                    f,
+                    "unknown tile id {} at row {}, column {}",
+                    tile, row, column
+                )
             }
+            LevelError::UnclosedOutline { row, column } => write!(
+                f,
+                "polygon outline does not close, it stops at the corner at row {}, column {}",
+                row, column
+            ),
         }
+    }
+}
 
-        return true;
+impl std::error::Error for LevelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LevelError::Io(err) => Some(err),
+            LevelError::InvalidUtf8(err) => Some(err),
+            LevelError::InvalidJson(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
-const LEVEL_DATA: &'static [u8] = include_bytes!("../assets/level.json");
+impl From<std::io::Error> for LevelError {
+    fn from(err: std::io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+impl From<std::str::Utf8Error> for LevelError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        LevelError::InvalidUtf8(err)
+    }
+}
+
+impl From<serde_json::Error> for LevelError {
+    fn from(err: serde_json::Error) -> Self {
+        LevelError::InvalidJson(err)
+    }
+}
+
+/// The highest tile id that has a shape
+const MAX_TILE_ID: usize = 9;
+
+/// Makes sure the tile grid is rectangular and only contains known tiles
+fn validate_level_grid(level_grid_data: &[Vec<usize>]) -> Result<(), LevelError> {
+    let row_length = match level_grid_data.first() {
+        Some(first_row) if !first_row.is_empty() => first_row.len(),
+        _ => return Err(LevelError::EmptyGrid),
+    };
+
+    for (row, row_data) in level_grid_data.iter().enumerate() {
+        if row_data.len() != row_length {
+            return Err(LevelError::RaggedRow {
+                row,
+                expected: row_length,
+                found: row_data.len(),
+            });
+        }
 
-pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
+        for (column, &tile) in row_data.iter().enumerate() {
+            if tile > MAX_TILE_ID {
+                return Err(LevelError::UnknownTile { row, column, tile });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The level that is used when no level file is given on the command line
+pub const DEFAULT_LEVEL_DATA: &[u8] = include_bytes!("../assets/level.json");
+
+pub fn generate_level_polygons(
+    level_data: &[u8],
+    grid_size: f32,
+) -> Result<(Vec<Polygon>, Vec2, Vec2), LevelError> {
     let mut rng = rand::thread_rng();
 
-    let res = std::str::from_utf8(LEVEL_DATA);
-    let level_grid_data: Vec<Vec<usize>> = serde_json::from_str(&res.unwrap()).unwrap();
+    let res = std::str::from_utf8(level_data)?;
+    let level_grid_data: Vec<Vec<usize>> = serde_json::from_str(res)?;
+
+    validate_level_grid(&level_grid_data)?;
 
     let size = Vec2::new(
         level_grid_data[0].len() as f32,
@@ -165,11 +395,11 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
     // Separate the lines into polygons
     let mut polygons: Vec<Polygon> = Vec::new();
 
-    let container_color = Color::rgb(
+    let container_color = [
         rng.gen_range(0.0..=1.0),
         rng.gen_range(0.0..=1.0),
         rng.gen_range(0.0..=1.0),
-    );
+    ];
 
     // While there are lines left
     while line_count > 0 {
@@ -192,6 +422,8 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
 
         // While the polygon is not closed
         while start_vert != current_vert {
+            let mut line_found = false;
+
             for i in 0..line_count {
                 let line_start = line_points[i * 2].clone();
                 let line_end = line_points[i * 2 + 1].clone();
@@ -212,6 +444,7 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
                     current_vert = line_end;
 
                     // Break out of the for loop
+                    line_found = true;
                     break;
                 }
                 // If the line ends at the current vertex
@@ -230,9 +463,18 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
                     current_vert = line_start;
 
                     // Break out of the for loop
+                    line_found = true;
                     break;
                 }
             }
+
+            // If no line continues from the current vertex the polygon can never close
+            if !line_found {
+                return Err(LevelError::UnclosedOutline {
+                    row: ((offset.y - current_vert.y) / grid_size).round() as usize,
+                    column: ((current_vert.x - offset.x) / grid_size).round() as usize,
+                });
+            }
         }
 
         let is_container = point_in_polygon(&polygon_lines, Vec2::new(0.0, 0.0));
@@ -248,11 +490,11 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
         let color = if is_container {
             container_color
         } else {
-            Color::rgb(
+            [
                 rng.gen_range(0.0..=1.0),
                 rng.gen_range(0.0..=1.0),
                 rng.gen_range(0.0..=1.0),
-            )
+            ]
         };
 
         // Add the polygon to the list of polygons
@@ -263,7 +505,7 @@ pub fn generate_level_polygons(grid_size: f32) -> (Vec<Polygon>, Vec2, Vec2) {
         });
     }
 
-    return (polygons, size, size / 2.0);
+    Ok((polygons, size, size / 2.0))
 }
 
 fn calculate_winding_order(vertices: &Vec<Vec2>) -> f32 {
@@ -298,12 +540,41 @@ fn point_in_polygon(polygon_lines: &Vec<Vec2>, point: Vec2) -> bool {
     return intersect_counter % 2 == 1;
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct PolygonLine {
     pub polygon_index: usize,
     pub line_index: usize,
 }
 
+/// One of the four edges of a grid cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TileEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Whether the shape of `tile` covers the whole of the given edge of its grid cell.
+/// Edges shared by two tiles that both cover them are culled, since they are inside the solid area.
+fn tile_has_full_edge(tile: usize, edge: TileEdge) -> bool {
+    match tile {
+        // Squares
+        1 => true,
+        // Right triangles
+        2 => matches!(edge, TileEdge::Bottom | TileEdge::Left),
+        3 => matches!(edge, TileEdge::Bottom | TileEdge::Right),
+        4 => matches!(edge, TileEdge::Top | TileEdge::Left),
+        5 => matches!(edge, TileEdge::Top | TileEdge::Right),
+        // Isosceles triangles
+        6 => edge == TileEdge::Bottom,
+        7 => edge == TileEdge::Top,
+        8 => edge == TileEdge::Left,
+        9 => edge == TileEdge::Right,
+        _ => false,
+    }
+}
+
 fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size: Vec2) -> Vec<Vec2> {
     let mut line_points: Vec<Vec2> = Vec::new();
 
@@ -316,7 +587,7 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                     // Squares
 
                     // Left edge
-                    if x == 0 || level_grid_data[y][x - 1] == 0 {
+                    if x == 0 || !tile_has_full_edge(level_grid_data[y][x - 1], TileEdge::Right) {
                         line_points.push(Vec2::new(
                             x as f32 * grid_cell_size,
                             y as f32 * grid_cell_size,
@@ -327,7 +598,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                         ));
                     }
                     // Right edge
-                    if x == level_grid_data[y].len() - 1 || level_grid_data[y][x + 1] == 0 {
+                    if x == level_grid_data[y].len() - 1
+                        || !tile_has_full_edge(level_grid_data[y][x + 1], TileEdge::Left)
+                    {
                         line_points.push(Vec2::new(
                             (x + 1) as f32 * grid_cell_size,
                             y as f32 * grid_cell_size,
@@ -338,7 +611,7 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                         ));
                     }
                     // Top edge
-                    if y == 0 || level_grid_data[y - 1][x] == 0 {
+                    if y == 0 || !tile_has_full_edge(level_grid_data[y - 1][x], TileEdge::Bottom) {
                         line_points.push(Vec2::new(
                             x as f32 * grid_cell_size,
                             y as f32 * grid_cell_size,
@@ -349,7 +622,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                         ));
                     }
                     // Bottom edge
-                    if y == size.y as usize - 1 || level_grid_data[y + 1][x] == 0 {
+                    if y == size.y as usize - 1
+                        || !tile_has_full_edge(level_grid_data[y + 1][x], TileEdge::Top)
+                    {
                         line_points.push(Vec2::new(
                             x as f32 * grid_cell_size,
                             (y + 1) as f32 * grid_cell_size,
@@ -380,7 +655,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             ));
 
                             // Bottom edge
-                            if y == size.y as usize - 1 || level_grid_data[y + 1][x] == 0 {
+                            if y == size.y as usize - 1
+                                || !tile_has_full_edge(level_grid_data[y + 1][x], TileEdge::Top)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     (y + 1) as f32 * grid_cell_size,
@@ -392,7 +669,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             }
 
                             // Left edge
-                            if x == 0 || level_grid_data[y][x - 1] == 0 {
+                            if x == 0
+                                || !tile_has_full_edge(level_grid_data[y][x - 1], TileEdge::Right)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -417,7 +696,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             ));
 
                             // Bottom edge
-                            if y == size.y as usize - 1 || level_grid_data[y + 1][x] == 0 {
+                            if y == size.y as usize - 1
+                                || !tile_has_full_edge(level_grid_data[y + 1][x], TileEdge::Top)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     (y + 1) as f32 * grid_cell_size,
@@ -429,7 +710,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             }
 
                             // Right edge
-                            if x == level_grid_data[y].len() - 1 || level_grid_data[y][x + 1] == 0 {
+                            if x == level_grid_data[y].len() - 1
+                                || !tile_has_full_edge(level_grid_data[y][x + 1], TileEdge::Left)
+                            {
                                 line_points.push(Vec2::new(
                                     (x + 1) as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -454,7 +737,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             ));
 
                             // Top edge
-                            if y == 0 || level_grid_data[y - 1][x] == 0 {
+                            if y == 0
+                                || !tile_has_full_edge(level_grid_data[y - 1][x], TileEdge::Bottom)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -466,7 +751,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             }
 
                             // Left edge
-                            if x == 0 || level_grid_data[y][x - 1] == 0 {
+                            if x == 0
+                                || !tile_has_full_edge(level_grid_data[y][x - 1], TileEdge::Right)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -491,7 +778,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             ));
 
                             // Top edge
-                            if y == 0 || level_grid_data[y - 1][x] == 0 {
+                            if y == 0
+                                || !tile_has_full_edge(level_grid_data[y - 1][x], TileEdge::Bottom)
+                            {
                                 line_points.push(Vec2::new(
                                     x as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -503,7 +792,9 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                             }
 
                             // Right edge
-                            if x == level_grid_data[y].len() - 1 || level_grid_data[y][x + 1] == 0 {
+                            if x == level_grid_data[y].len() - 1
+                                || !tile_has_full_edge(level_grid_data[y][x + 1], TileEdge::Left)
+                            {
                                 line_points.push(Vec2::new(
                                     (x + 1) as f32 * grid_cell_size,
                                     y as f32 * grid_cell_size,
@@ -518,189 +809,142 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
                     }
                 }
                 6..=9 => {
-                    // // Isosceles triangles
-
-                    // let triangle_type = tile - 6;
-
-                    // match triangle_type {
-                    //     0 => {
-                    //         // Bottom
-
-                    //         // Top left
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Top right
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 1.0) as f32 * grid_size,
-                    //                 (y + 1) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Bottom edge
-                    //         if y == json_data.len() - 1 || json_data[y + 1][x] == 0 {
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(
-                    //                     (x + 1) as f32 * grid_size,
-                    //                     (y + 1) as f32 * grid_size,
-                    //                 ),
-                    //                 Color::RED,
-                    //             ));
-                    //         }
-                    //     }
-                    //     1 => {
-                    //         // Top
-
-                    //         // Bottom left
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(x as f32 * grid_size, y as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Bottom right
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 1.0) as f32 * grid_size,
-                    //                 y as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Top edge
-                    //         if y == 0 || json_data[y - 1][x] == 0 {
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(x as f32 * grid_size, y as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //         }
-                    //     }
-                    //     2 => {
-                    //         // Left
-
-                    //         // Top right
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(x as f32 * grid_size, y as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Bottom right
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Left edge
-                    //         if x == 0 || json_data[y][x - 1] == 0 {
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(x as f32 * grid_size, y as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //         }
-                    //     }
-                    //     3 => {
-                    //         // Right
-
-                    //         // Top left
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Bottom left
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new((x + 1) as f32 * grid_size, (y + 1) as f32 * grid_size),
-                    //             Color::RED,
-                    //         ));
-                    //         line_points.push(Vertex::with_pos_color(
-                    //             Vec2::new(
-                    //                 (x as f32 + 0.5) as f32 * grid_size,
-                    //                 (y as f32 + 0.5) as f32 * grid_size,
-                    //             ),
-                    //             Color::RED,
-                    //         ));
-
-                    //         // Right edge
-                    //         if x == json_data[y].len() - 1 || json_data[y][x + 1] == 0 {
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size),
-                    //                 Color::RED,
-                    //             ));
-                    //             line_points.push(Vertex::with_pos_color(
-                    //                 Vec2::new(
-                    //                     (x + 1) as f32 * grid_size,
-                    //                     (y + 1) as f32 * grid_size,
-                    //                 ),
-                    //                 Color::RED,
-                    //             ));
-                    //         }
-                    //     }
-                    //     _ => {}
-                    // }
+                    // Isosceles triangles
+
+                    let triangle_type = tile - 6;
+
+                    let center = Vec2::new(
+                        (x as f32 + 0.5) * grid_cell_size,
+                        (y as f32 + 0.5) * grid_cell_size,
+                    );
+
+                    match triangle_type {
+                        0 => {
+                            // Bottom
+
+                            // Left side
+                            line_points.push(Vec2::new(
+                                x as f32 * grid_cell_size,
+                                (y + 1) as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Right side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_cell_size,
+                                (y + 1) as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Bottom edge
+                            if y == size.y as usize - 1
+                                || !tile_has_full_edge(level_grid_data[y + 1][x], TileEdge::Top)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_cell_size,
+                                    (y + 1) as f32 * grid_cell_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_cell_size,
+                                    (y + 1) as f32 * grid_cell_size,
+                                ));
+                            }
+                        }
+                        1 => {
+                            // Top
+
+                            // Left side
+                            line_points.push(Vec2::new(
+                                x as f32 * grid_cell_size,
+                                y as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Right side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_cell_size,
+                                y as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Top edge
+                            if y == 0
+                                || !tile_has_full_edge(level_grid_data[y - 1][x], TileEdge::Bottom)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_cell_size,
+                                    y as f32 * grid_cell_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_cell_size,
+                                    y as f32 * grid_cell_size,
+                                ));
+                            }
+                        }
+                        2 => {
+                            // Left
+
+                            // Top side
+                            line_points.push(Vec2::new(
+                                x as f32 * grid_cell_size,
+                                y as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Bottom side
+                            line_points.push(Vec2::new(
+                                x as f32 * grid_cell_size,
+                                (y + 1) as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Left edge
+                            if x == 0
+                                || !tile_has_full_edge(level_grid_data[y][x - 1], TileEdge::Right)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_cell_size,
+                                    y as f32 * grid_cell_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_cell_size,
+                                    (y + 1) as f32 * grid_cell_size,
+                                ));
+                            }
+                        }
+                        3 => {
+                            // Right
+
+                            // Top side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_cell_size,
+                                y as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Bottom side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_cell_size,
+                                (y + 1) as f32 * grid_cell_size,
+                            ));
+                            line_points.push(center);
+
+                            // Right edge
+                            if x == level_grid_data[y].len() - 1
+                                || !tile_has_full_edge(level_grid_data[y][x + 1], TileEdge::Left)
+                            {
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_cell_size,
+                                    y as f32 * grid_cell_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_cell_size,
+                                    (y + 1) as f32 * grid_cell_size,
+                                ));
+                            }
+                        }
+                        _ => {}
+                    }
                 }
                 _ => {}
             }
@@ -709,3 +953,101 @@ fn get_line_points(level_grid_data: Vec<Vec<usize>>, grid_cell_size: f32, size:
 
     return line_points;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A room 3 tiles across inside walls 1 tile thick, so the inner walls are at +-48 and the outer ones at +-80
+    const ROOM_LEVEL_DATA: &[u8] = b"[
+        [1, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 1, 1, 1, 1]
+    ]";
+
+    fn room_level() -> Level {
+        Level::from_json(ROOM_LEVEL_DATA, 32.0).unwrap()
+    }
+
+    #[test]
+    fn raycast_returns_the_closest_hit() {
+        let level = room_level();
+
+        let hit = level
+            .raycast(Vec2::ZERO, Vec2::new(100.0, 0.0))
+            .expect("the ray leaves the room");
+
+        assert!(
+            (hit.point - Vec2::new(48.0, 0.0)).length() < 1e-4,
+            "hit {}",
+            hit.point
+        );
+        assert!(
+            (hit.fraction - 0.48).abs() < 1e-6,
+            "fraction {}",
+            hit.fraction
+        );
+        assert!(hit.normal.x.abs() > 0.99, "normal {}", hit.normal);
+        assert_eq!(
+            level.get_line_normal(hit.line.polygon_index, hit.line.line_index),
+            Some(hit.normal)
+        );
+    }
+
+    #[test]
+    fn raycast_fraction_is_along_the_ray() {
+        let level = room_level();
+
+        let hit = level
+            .raycast(Vec2::new(0.0, 16.0), Vec2::new(0.0, -84.0))
+            .expect("the ray leaves the room through the floor");
+
+        // 64 of the ray's 100 units are inside the room
+        assert!(
+            (hit.fraction - 0.64).abs() < 1e-6,
+            "fraction {}",
+            hit.fraction
+        );
+        assert!(
+            (hit.point - Vec2::new(0.0, -48.0)).length() < 1e-4,
+            "hit {}",
+            hit.point
+        );
+    }
+
+    #[test]
+    fn raycast_ignoring_skips_ignored_lines() {
+        let level = room_level();
+        let (start, end) = (Vec2::ZERO, Vec2::new(100.0, 0.0));
+
+        let first_hit = level.raycast(start, end).unwrap();
+        let next_hit = level.raycast_ignoring(start, end, &[first_hit.line.clone()]);
+
+        // Straight through the inner wall to the outside of the level
+        let next_hit = next_hit.expect("the outer wall is still in the way");
+        assert_ne!(next_hit.line, first_hit.line);
+        assert!(next_hit.fraction > first_hit.fraction);
+        assert!(
+            (next_hit.point - Vec2::new(80.0, 0.0)).length() < 1e-4,
+            "hit {}",
+            next_hit.point
+        );
+
+        assert!(level
+            .raycast_ignoring(start, end, &[first_hit.line.clone(), next_hit.line.clone()])
+            .is_none());
+    }
+
+    #[test]
+    fn raycast_inside_the_room_hits_nothing() {
+        let level = room_level();
+
+        assert!(level
+            .raycast(Vec2::new(-40.0, -40.0), Vec2::new(40.0, 40.0))
+            .is_none());
+        assert!(level.line_of_sight_check(Vec2::new(-40.0, 0.0), Vec2::new(40.0, 0.0)));
+        assert!(!level.line_of_sight_check(Vec2::ZERO, Vec2::new(100.0, 0.0)));
+    }
+}
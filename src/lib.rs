@@ -0,0 +1,18 @@
+//! Level geometry, jump checking and navigation graph building for 2D platformers.
+//!
+//! Everything works on plain `glam` types. The Bevy plugins and gizmo systems are behind the `render` feature.
+
+pub mod graph_bake;
+#[cfg(feature = "render")]
+pub mod jump_check;
+pub mod jump_profile;
+pub mod jump_solver;
+pub mod level;
+pub mod node_grid;
+#[cfg(feature = "render")]
+pub mod path_requests;
+pub mod pathfinding;
+pub mod segment_grid;
+pub mod surface_path;
+pub mod svg_export;
+pub mod utils;
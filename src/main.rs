@@ -1,127 +1,80 @@
-mod jump_check;
-mod level;
-mod pathfinding;
-mod utils;
+mod cli;
+#[cfg(feature = "render")]
+mod debug_view;
 
-use bevy::prelude::*;
+use cli::{parse_args, run_headless, CliCommand, USAGE};
+#[cfg(feature = "render")]
+use debug_view::run_debug_view;
+use platformer_jump_checker::{
+    graph_bake::GraphBake,
+    jump_profile::JumpProfile,
+    level::{Level, DEFAULT_LEVEL_DATA},
+};
 
-use bevy::window::PrimaryWindow;
-use bevy::{app::AppExit, window::PresentMode};
-use jump_check::JumpCheckPlugin;
-use level::{generate_level_polygons, Level};
-use pathfinding::{init_pathfinding_graph, Pathfinding, PathfindingPlugin};
-
-pub const GRAVITY_STRENGTH: f32 = 0.5;
+pub const GRID_SIZE: f32 = 32.0;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Platformer Jump Checker".to_string(),
-                present_mode: PresentMode::AutoVsync,
-                focused: true,
-                ..default()
-            }),
-            ..default()
-        }))
-        .add_plugins(PathfindingPlugin)
-        .add_plugins(JumpCheckPlugin)
-        // Startup systems
-        .add_systems(Startup, s_init)
-        // Update systems
-        .add_systems(Update, s_input)
-        .add_systems(Update, s_render)
-        .run();
-}
-
-pub fn s_init(mut commands: Commands, pathfinding: ResMut<Pathfinding>) {
-    let grid_size = 32.0;
-
-    let (level_polygons, size, half_size) = generate_level_polygons(grid_size);
-
-    let level = Level {
-        polygons: level_polygons,
-        grid_size,
-        size,
-        half_size,
-    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    init_pathfinding_graph(&level, pathfinding);
-
-    commands.insert_resource(level);
-
-    commands.spawn(Camera2dBundle::default());
-}
-
-pub fn s_input(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut exit: EventWriter<AppExit>,
-    mouse_input: Res<ButtonInput<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
-    mut pathfinding: ResMut<Pathfinding>,
-) {
-    // Escape to exit (if not WASM)
-    #[cfg(not(target_arch = "wasm32"))]
-    if keyboard_input.just_pressed(KeyCode::Escape) {
-        exit.send(AppExit);
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
     }
 
-    // Select start node with left click
-    if mouse_input.just_pressed(MouseButton::Left) {
-        let window_size = q_windows.single().resolution.clone();
-        if let Some(position) = q_windows.single().cursor_position() {
-            let mut mouse_pos_world =
-                position - Vec2::new(window_size.width() / 2.0, window_size.height() / 2.0);
-            mouse_pos_world.y *= -1.0;
+    let cli_args = parse_args(args).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        std::process::exit(1);
+    });
+
+    // Load the level given on the command line, falling back to the built in one
+    let level = match cli_args.level_path {
+        Some(level_path) => Level::load(&level_path, GRID_SIZE).unwrap_or_else(|err| {
+            eprintln!("Failed to load level \"{}\": {}", level_path, err);
+            std::process::exit(1);
+        }),
+        None => Level::from_json(DEFAULT_LEVEL_DATA, GRID_SIZE)
+            .expect("The built in level should always be valid"),
+    };
 
-            for node_index in 0..pathfinding.nodes.len() {
-                let node = &pathfinding.nodes[node_index];
+    // Load the jump profile given on the command line, falling back to the defaults
+    let profile = match cli_args.profile_path {
+        Some(profile_path) => JumpProfile::load(&profile_path).unwrap_or_else(|err| {
+            eprintln!("Failed to load jump profile \"{}\": {}", profile_path, err);
+            std::process::exit(1);
+        }),
+        None => JumpProfile::default(),
+    };
 
-                if (mouse_pos_world - node.position).length_squared() < (3.5_f32).powi(2) {
-                    pathfinding.start_graph_node = Some(node.clone());
-                }
+    // Load the baked graph given on the command line, making sure it matches the level and profile
+    let graph_bake = cli_args.graph_path.map(|graph_path| {
+        GraphBake::load(&graph_path)
+            .and_then(|graph_bake| graph_bake.check(&level, &profile).map(|_| graph_bake))
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to load graph \"{}\": {}", graph_path, err);
+                std::process::exit(1);
+            })
+    });
+
+    // Answer queries without opening a window
+    if cli_args.command != CliCommand::Window {
+        match run_headless(&cli_args.command, &level, &profile, graph_bake) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
             }
         }
+        return;
     }
-    // Select goal node with right click
-    if mouse_input.just_pressed(MouseButton::Right) {
-        let window_size = q_windows.single().resolution.clone();
-        if let Some(position) = q_windows.single().cursor_position() {
-            let mut mouse_pos_world =
-                position - Vec2::new(window_size.width() / 2.0, window_size.height() / 2.0);
-            mouse_pos_world.y *= -1.0;
-
-            for node_index in 0..pathfinding.nodes.len() {
-                let node = &pathfinding.nodes[node_index];
 
-                if (mouse_pos_world - node.position).length_squared() < (7.5_f32).powi(2) {
-                    pathfinding.goal_graph_node = Some(node.clone());
-                }
-            }
-        }
-    }
+    run_debug_view(level, profile, graph_bake);
 }
 
-pub fn s_render(mut gizmos: Gizmos, level: Res<Level>, pathfinding: Res<Pathfinding>) {
-    // Draw the level polygons
-    for polygon in &level.polygons {
-        gizmos.linestrip_2d(
-            polygon.points.iter().cloned().collect::<Vec<Vec2>>(),
-            polygon.color.with_a(0.1),
-        );
-    }
-
-    // Draw the pathfinding nodes
-    for node in &pathfinding.nodes {
-        gizmos.circle_2d(node.position, 2.5, Color::WHITE);
-    }
-
-    // Draw a larger circle for the start and end nodes
-    if let Some(start_graph_node) = &pathfinding.start_graph_node {
-        gizmos.circle_2d(start_graph_node.position, 2.5, Color::GREEN);
-    }
-    if let Some(goal_graph_node) = &pathfinding.goal_graph_node {
-        gizmos.circle_2d(goal_graph_node.position, 2.5, Color::YELLOW);
-    }
+#[cfg(not(feature = "render"))]
+fn run_debug_view(_level: Level, _profile: JumpProfile, _graph_bake: Option<GraphBake>) {
+    eprintln!(
+        "The debug view needs the render feature, use one of the headless commands instead\n\n{}",
+        USAGE
+    );
+    std::process::exit(1);
 }
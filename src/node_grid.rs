@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2};
+
+use crate::pathfinding::PathfindingGraphNode;
+
+/// The size of the cells nodes are sorted into, about one level tile
+const NODE_GRID_CELL_SIZE: f32 = 32.0;
+
+/// A uniform grid over the graph nodes, so finding the node nearest a point doesn't test every node
+#[derive(Debug, Clone)]
+pub struct NodeGrid {
+    cell_size: f32,
+    /// The ids of the nodes in each cell that has any
+    cells: HashMap<IVec2, Vec<usize>>,
+    /// The lowest and highest cell coordinates holding a node
+    min_cell: IVec2,
+    max_cell: IVec2,
+}
+
+impl Default for NodeGrid {
+    fn default() -> Self {
+        NodeGrid {
+            cell_size: NODE_GRID_CELL_SIZE,
+            cells: HashMap::new(),
+            min_cell: IVec2::ZERO,
+            max_cell: IVec2::ZERO,
+        }
+    }
+}
+
+impl NodeGrid {
+    pub fn new(nodes: &[PathfindingGraphNode]) -> NodeGrid {
+        let mut grid = NodeGrid {
+            min_cell: IVec2::MAX,
+            max_cell: IVec2::MIN,
+            ..Default::default()
+        };
+
+        for (node_id, node) in nodes.iter().enumerate() {
+            let cell = grid.cell_at(node.position);
+
+            grid.cells.entry(cell).or_default().push(node_id);
+            grid.min_cell = grid.min_cell.min(cell);
+            grid.max_cell = grid.max_cell.max(cell);
+        }
+
+        grid
+    }
+
+    /// The id of the closest node to `position` that's no further than `max_dist` and passes `filter`
+    pub fn nearest(
+        &self,
+        nodes: &[PathfindingGraphNode],
+        position: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&PathfindingGraphNode) -> bool,
+    ) -> Option<usize> {
+        if self.cells.is_empty() {
+            return None;
+        }
+
+        let center_cell = self.cell_at(position);
+
+        // Rings past this one can't hold any nodes
+        let last_ring = [
+            center_cell.x - self.min_cell.x,
+            self.max_cell.x - center_cell.x,
+            center_cell.y - self.min_cell.y,
+            self.max_cell.y - center_cell.y,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0)
+        .max(0);
+
+        let mut nearest: Option<(usize, f32)> = None;
+
+        // Search outwards in square rings of cells around the one the position is in
+        for ring in 0..=last_ring {
+            for cell in ring_cells(center_cell, ring) {
+                let Some(node_ids) = self.cells.get(&cell) else {
+                    continue;
+                };
+
+                for &node_id in node_ids {
+                    let dist = (nodes[node_id].position - position).length();
+
+                    if dist <= max_dist
+                        && nearest.is_none_or(|(_, nearest_dist)| dist < nearest_dist)
+                        && filter(&nodes[node_id])
+                    {
+                        nearest = Some((node_id, dist));
+                    }
+                }
+            }
+
+            // Every node in the next ring is at least this far away
+            let next_ring_dist = ring as f32 * self.cell_size;
+            let search_dist = nearest.map_or(max_dist, |(_, nearest_dist)| nearest_dist);
+
+            if next_ring_dist > search_dist {
+                break;
+            }
+        }
+
+        nearest.map(|(node_id, _)| node_id)
+    }
+
+    fn cell_at(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+}
+
+/// The cells exactly `ring` cells away from `center` in either direction
+fn ring_cells(center: IVec2, ring: i32) -> Vec<IVec2> {
+    if ring == 0 {
+        return vec![center];
+    }
+
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+
+    for offset in -ring..=ring {
+        cells.push(center + IVec2::new(offset, -ring));
+        cells.push(center + IVec2::new(offset, ring));
+    }
+    for offset in (-ring + 1)..ring {
+        cells.push(center + IVec2::new(-ring, offset));
+        cells.push(center + IVec2::new(ring, offset));
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: usize, position: Vec2) -> PathfindingGraphNode {
+        PathfindingGraphNode {
+            id,
+            position,
+            polygon_index: 0,
+            line_indicies: Vec::new(),
+            walkable_connections: Vec::new(),
+            jumpable_connections: Vec::new(),
+            droppable_connections: Vec::new(),
+            normal: Vec2::Y,
+            is_corner: false,
+            is_external_corner: None,
+        }
+    }
+
+    /// Two clusters of nodes either side of the origin with a wide band of empty cells between them
+    fn nodes() -> Vec<PathfindingGraphNode> {
+        let mut positions = Vec::new();
+
+        for i in 0..12 {
+            let i = i as f32;
+            positions.push(Vec2::new(-150.0 + i * 7.3, -40.0 + i * i * 1.7));
+            positions.push(Vec2::new(90.0 + i * 11.1, 20.0 - i * 9.4));
+        }
+        // Alone in the middle of the gap
+        positions.push(Vec2::new(3.0, 250.0));
+
+        positions
+            .into_iter()
+            .enumerate()
+            .map(|(id, position)| node(id, position))
+            .collect()
+    }
+
+    fn brute_force_nearest(
+        nodes: &[PathfindingGraphNode],
+        position: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&PathfindingGraphNode) -> bool,
+    ) -> Option<f32> {
+        nodes
+            .iter()
+            .filter(|node| filter(node))
+            .map(|node| (node.position - position).length())
+            .filter(|dist| *dist <= max_dist)
+            .min_by(f32::total_cmp)
+    }
+
+    fn check_nearest(
+        grid: &NodeGrid,
+        nodes: &[PathfindingGraphNode],
+        position: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&PathfindingGraphNode) -> bool + Copy,
+    ) {
+        let expected = brute_force_nearest(nodes, position, max_dist, filter);
+        let found = grid.nearest(nodes, position, max_dist, filter);
+
+        // Compared by distance, as two nodes can be equally close
+        let found_dist = found.map(|node_id| (nodes[node_id].position - position).length());
+        assert_eq!(
+            found_dist, expected,
+            "nearest to {} within {} was {:?}",
+            position, max_dist, found
+        );
+
+        if let Some(node_id) = found {
+            assert!(filter(&nodes[node_id]));
+        }
+    }
+
+    #[test]
+    fn nearest_matches_a_brute_force_search() {
+        let nodes = nodes();
+        let grid = NodeGrid::new(&nodes);
+
+        // From well outside the nodes' bounds on every side, through the empty cells between the clusters
+        for x in (-400..=450).step_by(23) {
+            for y in (-350..=500).step_by(29) {
+                let position = Vec2::new(x as f32, y as f32);
+
+                for max_dist in [10.0, 60.0, 200.0, f32::INFINITY] {
+                    check_nearest(&grid, &nodes, position, max_dist, |_| true);
+                    check_nearest(&grid, &nodes, position, max_dist, |node| node.id % 3 == 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_finds_nothing_without_nodes() {
+        let grid = NodeGrid::new(&[]);
+
+        assert_eq!(grid.nearest(&[], Vec2::ZERO, f32::INFINITY, |_| true), None);
+
+        let nodes = nodes();
+        let grid = NodeGrid::new(&nodes);
+
+        assert_eq!(
+            grid.nearest(&nodes, Vec2::ZERO, f32::INFINITY, |_| false),
+            None
+        );
+    }
+}
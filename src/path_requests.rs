@@ -0,0 +1,81 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        system::{Commands, Res, ResMut},
+    },
+    math::Vec2,
+};
+
+use crate::{
+    jump_profile::JumpProfile, level::Level, pathfinding::Pathfinding, surface_path::SurfacePath,
+};
+
+/// Answers `PathRequest` events with `PathResult` events, so any number of agents can ask for paths
+pub struct PathRequestPlugin;
+
+impl Plugin for PathRequestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PathRequest>();
+        app.add_event::<PathResult>();
+        // After every Update system, so the requests sent there are all answered the same frame
+        app.add_systems(PostUpdate, s_answer_path_requests);
+    }
+}
+
+/// Asks for a path between two positions on the level surface.
+/// Requests sent during `Update` are answered with a `PathResult` in `PostUpdate` of the same frame,
+/// so systems in `Update` read the result the frame after.
+#[derive(Event, Debug, Clone)]
+pub struct PathRequest {
+    /// The agent asking, which the result is sent back to
+    pub entity: Entity,
+    pub from: Vec2,
+    pub to: Vec2,
+    /// The agent's own jump profile, leave it out to use the one the graph was built with
+    pub profile: Option<JumpProfile>,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct PathResult {
+    pub entity: Entity,
+    /// `None` when there's no surface near either position, or no way between them
+    pub path: Option<SurfacePath>,
+}
+
+/// The latest path found for the entity, removed when a request for it fails
+#[derive(Component, Debug, Clone)]
+pub struct Path(pub SurfacePath);
+
+pub fn s_answer_path_requests(
+    mut commands: Commands,
+    mut path_requests: EventReader<PathRequest>,
+    mut path_results: EventWriter<PathResult>,
+    level: Res<Level>,
+    profile: Res<JumpProfile>,
+    mut pathfinding: ResMut<Pathfinding>,
+) {
+    for request in path_requests.read() {
+        let path = pathfinding.find_path_between_points(
+            &level,
+            request.profile.as_ref().unwrap_or(&profile),
+            request.from,
+            request.to,
+        );
+
+        // The agent may have been despawned since it asked
+        if let Some(mut entity_commands) = commands.get_entity(request.entity) {
+            match &path {
+                Some(path) => entity_commands.insert(Path(path.clone())),
+                None => entity_commands.remove::<Path>(),
+            };
+        }
+
+        path_results.send(PathResult {
+            entity: request.entity,
+            path,
+        });
+    }
+}
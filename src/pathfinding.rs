@@ -1,60 +1,173 @@
+use std::{cmp::Ordering, collections::BinaryHeap, fmt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+#[cfg(feature = "render")]
 use bevy::{
     app::{App, Plugin},
-    ecs::system::{ResMut, Resource},
-    math::Vec2,
+    ecs::system::Resource,
+};
+use bevy_tasks::{ComputeTaskPool, TaskPool};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "render")]
+use crate::path_requests::PathRequestPlugin;
+use crate::{
+    jump_profile::JumpProfile,
+    jump_solver::{JumpLaunch, JumpSolver},
+    level::{Level, PolygonLine},
+    node_grid::NodeGrid,
 };
 
-use crate::{level::Level, utils::line_intersect, GRAVITY_STRENGTH};
-
-pub const JUMP_V_MAX: f32 = 8.0;
-
+#[cfg(feature = "render")]
 pub struct PathfindingPlugin;
 
+#[cfg(feature = "render")]
 impl Plugin for PathfindingPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Pathfinding {
-            nodes: Vec::new(),
-            goal_graph_node: None,
-            start_graph_node: None,
-            active: false,
-        });
+        app.init_resource::<JumpProfile>();
+        app.init_resource::<Pathfinding>();
+        app.add_plugins(PathRequestPlugin);
     }
 }
 
-pub fn init_pathfinding_graph(level: &Level, mut pathfinding: ResMut<Pathfinding>) {
-    place_nodes(&mut pathfinding, level);
+pub fn init_pathfinding_graph(
+    level: &Level,
+    profile: &JumpProfile,
+    pathfinding: &mut Pathfinding,
+) -> GraphBuildReport {
+    let mut report = GraphBuildReport::default();
+
+    report.time_stage("place_nodes", || place_nodes(pathfinding, level));
+
+    report.time_stage("make_walkable_connections_2_way", || {
+        make_walkable_connections_2_way(pathfinding)
+    });
+
+    report.time_stage("remove_duplicate_nodes", || {
+        remove_duplicate_nodes(pathfinding)
+    });
+
+    report.time_stage("make_node_ids_indices", || {
+        make_node_ids_indices(pathfinding)
+    });
+
+    report.jump_pairs = report.time_stage("make_jumpable_connections", || {
+        make_jumpable_connections(pathfinding, level, profile)
+    });
+
+    report.time_stage("calculate_normals", || {
+        calculate_normals(pathfinding, level)
+    });
+
+    report.time_stage("setup_corners", || setup_corners(pathfinding));
+
+    report.time_stage("make_droppable_connections", || {
+        make_droppable_connections(pathfinding, level, profile)
+    });
+
+    report.time_stage("update_node_grid", || pathfinding.update_node_grid());
+
+    report
+}
+
+/// How long each stage of building the graph took, and how many node pairs were culled before jump checking
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphBuildReport {
+    pub stages: Vec<GraphBuildStage>,
+    pub jump_pairs: JumpPairCounts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphBuildStage {
+    pub name: &'static str,
+    pub milliseconds: f64,
+}
+
+impl GraphBuildReport {
+    fn time_stage<T>(&mut self, name: &'static str, stage: impl FnOnce() -> T) -> T {
+        let start_time = Instant::now();
+        let result = stage();
 
-    make_walkable_connections_2_way(&mut pathfinding);
+        self.stages.push(GraphBuildStage {
+            name,
+            milliseconds: start_time.elapsed().as_secs_f64() * 1000.0,
+        });
 
-    remove_duplicate_nodes(&mut pathfinding);
+        result
+    }
+
+    pub fn total_milliseconds(&self) -> f64 {
+        self.stages.iter().map(|stage| stage.milliseconds).sum()
+    }
+}
 
-    make_node_ids_indices(&mut pathfinding);
+impl fmt::Display for GraphBuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Built the graph in {:.1} ms", self.total_milliseconds())?;
 
-    make_jumpable_connections(&mut pathfinding, level);
+        for stage in &self.stages {
+            writeln!(f, "    {}: {:.1} ms", stage.name, stage.milliseconds)?;
+        }
 
-    calculate_normals(&mut pathfinding, level);
+        write!(
+            f,
+            "    {} jump candidates, {} in reach, {} jumpable",
+            self.jump_pairs.candidates, self.jump_pairs.in_reach, self.jump_pairs.jumpable
+        )
+    }
+}
 
-    setup_corners(&mut pathfinding);
+/// How many node pairs made it through each check in `make_jumpable_connections`
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct JumpPairCounts {
+    /// Pairs of nodes on different polygons
+    pub candidates: usize,
+    /// Candidates inside the envelope of the maximum launch speed
+    pub in_reach: usize,
+    /// Pairs in reach with a trajectory that doesn't hit the level
+    pub jumpable: usize,
+}
 
-    // make_droppable_connections(&mut pathfinding, level);
+impl std::ops::AddAssign for JumpPairCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.candidates += other.candidates;
+        self.in_reach += other.in_reach;
+        self.jumpable += other.jumpable;
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PathfindingGraphConnectionType {
     Walkable,
     Jumpable,
     Droppable,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathfindingGraphConnection {
     pub node_id: usize,
     pub dist: f32,
     pub connection_type: PathfindingGraphConnectionType,
     pub effort: f32,
+    /// How to make the jump, only set on jumpable connections
+    pub jump: Option<JumpLaunch>,
+    /// The velocity to walk or run off the ledge with, only set on droppable connections
+    pub drop_velocity: Option<Vec2>,
+}
+
+impl PathfindingGraphConnection {
+    /// The cost of traversing this connection, which is never less than the straight line distance
+    pub fn cost(&self) -> f32 {
+        self.dist + self.effort
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathfindingGraphNode {
     pub id: usize,
     pub position: Vec2,
@@ -68,12 +181,198 @@ pub struct PathfindingGraphNode {
     pub is_external_corner: Option<bool>,
 }
 
-#[derive(Resource)]
+impl PathfindingGraphNode {
+    /// The level lines this node sits on
+    pub fn polygon_lines(&self) -> Vec<PolygonLine> {
+        self.line_indicies
+            .iter()
+            .map(|&line_index| PolygonLine {
+                polygon_index: self.polygon_index,
+                line_index,
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "render", derive(Resource))]
 pub struct Pathfinding {
     pub nodes: Vec<PathfindingGraphNode>,
     pub goal_graph_node: Option<PathfindingGraphNode>,
     pub start_graph_node: Option<PathfindingGraphNode>,
+    pub path: Option<PathfindingPath>,
     pub active: bool,
+    /// Broad phase for `nearest_node`, rebuilt with `update_node_grid` whenever `nodes` changes
+    node_grid: NodeGrid,
+}
+
+/// An ordered list of hops through the graph, from a start node to a goal node
+#[derive(Debug, Clone, Serialize)]
+pub struct PathfindingPath {
+    pub start_node_id: usize,
+    pub connections: Vec<PathfindingGraphConnection>,
+    pub cost: f32,
+}
+
+impl PathfindingPath {
+    /// The ids of every node visited by the path, including the start node
+    pub fn node_ids(&self) -> Vec<usize> {
+        let mut node_ids = vec![self.start_node_id];
+        node_ids.extend(self.connections.iter().map(|connection| connection.node_id));
+        node_ids
+    }
+}
+
+/// An entry in the A* open set, ordered so that the `BinaryHeap` pops the lowest f-score first
+struct OpenSetEntry {
+    node_id: usize,
+    f_score: f32,
+}
+
+impl PartialEq for OpenSetEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.f_score == other.f_score
+    }
+}
+
+impl Eq for OpenSetEntry {}
+
+impl PartialOrd for OpenSetEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenSetEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f_score.total_cmp(&self.f_score)
+    }
+}
+
+impl Pathfinding {
+    /// Wraps an already built graph, like one loaded from a bake
+    pub fn from_nodes(nodes: Vec<PathfindingGraphNode>) -> Pathfinding {
+        let mut pathfinding = Pathfinding {
+            nodes,
+            ..Default::default()
+        };
+        pathfinding.update_node_grid();
+
+        pathfinding
+    }
+
+    /// Sorts the nodes into the grid used by `nearest_node`, call this after changing `nodes`
+    pub fn update_node_grid(&mut self) {
+        self.node_grid = NodeGrid::new(&self.nodes);
+    }
+
+    /// The closest node to `position` that's no further than `max_dist` and passes `filter`.
+    /// Pass `f32::INFINITY` as `max_dist` to snap to the graph from anywhere.
+    pub fn nearest_node(
+        &self,
+        position: Vec2,
+        max_dist: f32,
+        filter: impl Fn(&PathfindingGraphNode) -> bool,
+    ) -> Option<&PathfindingGraphNode> {
+        let node_id = self
+            .node_grid
+            .nearest(&self.nodes, position, max_dist, filter)?;
+
+        Some(&self.nodes[node_id])
+    }
+
+    /// Recalculates the path between the selected start and goal nodes
+    pub fn update_path(&mut self) {
+        self.path = match (&self.start_graph_node, &self.goal_graph_node) {
+            (Some(start_graph_node), Some(goal_graph_node)) => {
+                self.find_path(start_graph_node.id, goal_graph_node.id)
+            }
+            _ => None,
+        };
+    }
+
+    /// Finds the cheapest path between two nodes using A*, mixing walkable and jumpable connections
+    pub fn find_path(&self, start_node_id: usize, goal_node_id: usize) -> Option<PathfindingPath> {
+        self.find_path_with(start_node_id, goal_node_id, |_| true)
+    }
+
+    /// Like `find_path`, but only follows the connections that pass `connection_filter`
+    pub fn find_path_with(
+        &self,
+        start_node_id: usize,
+        goal_node_id: usize,
+        connection_filter: impl Fn(&PathfindingGraphConnection) -> bool,
+    ) -> Option<PathfindingPath> {
+        if start_node_id >= self.nodes.len() || goal_node_id >= self.nodes.len() {
+            return None;
+        }
+
+        let goal_pos = self.nodes[goal_node_id].position;
+
+        let mut g_scores = vec![f32::INFINITY; self.nodes.len()];
+        let mut came_from: Vec<Option<(usize, PathfindingGraphConnection)>> =
+            vec![None; self.nodes.len()];
+        let mut open_set = BinaryHeap::new();
+
+        g_scores[start_node_id] = 0.0;
+        open_set.push(OpenSetEntry {
+            node_id: start_node_id,
+            f_score: (goal_pos - self.nodes[start_node_id].position).length(),
+        });
+
+        while let Some(OpenSetEntry { node_id, f_score }) = open_set.pop() {
+            if node_id == goal_node_id {
+                break;
+            }
+
+            // Skip stale entries that have since been reached more cheaply
+            let node = &self.nodes[node_id];
+            if f_score > g_scores[node_id] + (goal_pos - node.position).length() {
+                continue;
+            }
+
+            let connections = node
+                .walkable_connections
+                .iter()
+                .chain(node.jumpable_connections.iter())
+                .chain(node.droppable_connections.iter())
+                .filter(|connection| connection_filter(connection));
+
+            for connection in connections {
+                let tentative_g_score = g_scores[node_id] + connection.cost();
+
+                if tentative_g_score < g_scores[connection.node_id] {
+                    g_scores[connection.node_id] = tentative_g_score;
+                    came_from[connection.node_id] = Some((node_id, connection.clone()));
+
+                    open_set.push(OpenSetEntry {
+                        node_id: connection.node_id,
+                        f_score: tentative_g_score
+                            + (goal_pos - self.nodes[connection.node_id].position).length(),
+                    });
+                }
+            }
+        }
+
+        if !g_scores[goal_node_id].is_finite() {
+            return None;
+        }
+
+        // Walk back from the goal to rebuild the list of hops
+        let mut connections = Vec::new();
+        let mut current_node_id = goal_node_id;
+        while let Some((previous_node_id, connection)) = &came_from[current_node_id] {
+            connections.push(connection.clone());
+            current_node_id = *previous_node_id;
+        }
+        connections.reverse();
+
+        Some(PathfindingPath {
+            start_node_id,
+            connections,
+            cost: g_scores[goal_node_id],
+        })
+    }
 }
 
 pub fn place_nodes(pathfinding: &mut Pathfinding, level: &Level) {
@@ -128,6 +427,8 @@ pub fn place_nodes(pathfinding: &mut Pathfinding, level: &Level) {
                                 dist: dist_between_nodes_on_line,
                                 connection_type: PathfindingGraphConnectionType::Walkable,
                                 effort: 0.0,
+                                jump: None,
+                                drop_velocity: None,
                             });
                     }
 
@@ -143,6 +444,8 @@ pub fn place_nodes(pathfinding: &mut Pathfinding, level: &Level) {
                         dist: dist_between_nodes_on_line,
                         connection_type: PathfindingGraphConnectionType::Walkable,
                         effort: 0.0,
+                        jump: None,
+                        drop_velocity: None,
                     }],
                     jumpable_connections: Vec::new(),
                     droppable_connections: Vec::new(),
@@ -173,6 +476,8 @@ pub fn make_walkable_connections_2_way(pathfinding: &mut Pathfinding) {
                     dist: connection.dist,
                     connection_type: PathfindingGraphConnectionType::Walkable,
                     effort: 0.0,
+                    jump: None,
+                    drop_velocity: None,
                 });
         }
     }
@@ -249,138 +554,124 @@ pub fn make_node_ids_indices(pathfinding: &mut Pathfinding) {
     }
 }
 
-pub fn make_jumpable_connections(pathfinding: &mut Pathfinding, level: &Level) {
-    for i in 0..pathfinding.nodes.len() {
-        let main_node = &pathfinding.nodes[i];
-
-        let mut jumpable_connections: Vec<PathfindingGraphConnection> = Vec::new();
-
-        'other_nodes: for j in 0..pathfinding.nodes.len() {
-            // Make sure we're not comparing the same node
-            if i == j {
-                continue;
-            }
-
-            let other_node = &pathfinding.nodes[j];
+/// How many nodes each parallel task finds the jumps from
+const JUMP_NODES_PER_TASK: usize = 16;
 
-            // Make sure the nodes are not on the same polygon
-            if main_node.polygon_index == other_node.polygon_index {
-                continue;
-            }
-
-            for polygon_index in 0..level.polygons.len() {
-                let polygon = &level.polygons[polygon_index];
-
-                'polygon_lines: for line_index in 1..polygon.points.len() {
-                    if main_node.polygon_index == polygon_index
-                        && main_node.line_indicies.contains(&(line_index - 1))
-                        || other_node.polygon_index == polygon_index
-                            && other_node.line_indicies.contains(&(line_index - 1))
-                    {
-                        continue 'polygon_lines;
-                    }
-
-                    let start = polygon.points[line_index - 1];
-                    let end = polygon.points[line_index];
-
-                    let intersection =
-                        line_intersect(start, end, main_node.position, other_node.position);
+pub fn make_jumpable_connections(
+    pathfinding: &mut Pathfinding,
+    level: &Level,
+    profile: &JumpProfile,
+) -> JumpPairCounts {
+    let jump_solver = JumpSolver::new(profile);
+    let nodes = &pathfinding.nodes;
 
-                    if intersection.is_some() {
-                        continue 'other_nodes;
-                    }
-                }
-            }
+    // Bevy sets the pool up when running as a plugin, headless builds make their own
+    let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
 
-            let jumpable_velocity = jumpability_test(main_node, other_node, level);
+    let task_results = task_pool.scope(|scope| {
+        for first_node_id in (0..nodes.len()).step_by(JUMP_NODES_PER_TASK) {
+            let jump_solver = &jump_solver;
 
-            if jumpable_velocity.is_none() {
-                continue 'other_nodes;
-            }
+            scope.spawn(async move {
+                let last_node_id = (first_node_id + JUMP_NODES_PER_TASK).min(nodes.len());
 
-            jumpable_connections.push(PathfindingGraphConnection {
-                node_id: j,
-                dist: (main_node.position - other_node.position).length(),
-                connection_type: PathfindingGraphConnectionType::Jumpable,
-                effort: jumpable_velocity.unwrap(),
+                (first_node_id..last_node_id)
+                    .map(|i| (i, find_jumpable_connections(i, nodes, level, jump_solver)))
+                    .collect::<Vec<_>>()
             });
         }
+    });
+
+    let mut pair_counts = JumpPairCounts::default();
 
+    for (i, (jumpable_connections, node_pair_counts)) in task_results.into_iter().flatten() {
         pathfinding.nodes[i].jumpable_connections = jumpable_connections;
+        pair_counts += node_pair_counts;
     }
+
+    pair_counts
 }
 
-fn jumpability_test(
-    main_node: &PathfindingGraphNode,
-    other_node: &PathfindingGraphNode,
+/// The jumps from one node to every other node it can reach without hitting the level
+fn find_jumpable_connections(
+    i: usize,
+    nodes: &[PathfindingGraphNode],
     level: &Level,
-) -> Option<f32> {
-    let start_pos = main_node.position;
-    let goal_pos = other_node.position;
-
-    let delta_p = goal_pos - start_pos;
-    let acceleration = Vec2::new(0.0, -GRAVITY_STRENGTH);
-    let v_max = JUMP_V_MAX;
-    let b1 = delta_p.dot(acceleration) + v_max * v_max;
-    let discriminant = b1 * b1 - acceleration.dot(acceleration) * delta_p.dot(delta_p);
-
-    if discriminant < 0.0 {
-        return None;
-    }
+    jump_solver: &JumpSolver,
+) -> (Vec<PathfindingGraphConnection>, JumpPairCounts) {
+    let main_node = &nodes[i];
 
-    let t_low_energy = (4.0 * delta_p.dot(delta_p) / acceleration.dot(acceleration))
-        .sqrt()
-        .sqrt();
-    let launch_velocity = delta_p / t_low_energy - acceleration * t_low_energy / 2.0;
-    let timestep = t_low_energy / 10 as f32;
+    let mut jumpable_connections: Vec<PathfindingGraphConnection> = Vec::new();
+    let mut pair_counts = JumpPairCounts::default();
 
-    let mut prev_pos = start_pos;
-
-    for i in 1..10 {
-        let current_jump_time = i as f32 * timestep;
-        let position = start_pos
-            + launch_velocity * current_jump_time
-            + acceleration * current_jump_time * current_jump_time / 2.0;
+    for (j, other_node) in nodes.iter().enumerate() {
+        // Make sure we're not comparing the same node
+        if i == j {
+            continue;
+        }
 
-        for polygon_index in 0..level.polygons.len() {
-            let polygon = &level.polygons[polygon_index];
+        // Make sure the nodes are not on the same polygon
+        if main_node.polygon_index == other_node.polygon_index {
+            continue;
+        }
 
-            'polygon_lines: for line_index in 1..polygon.points.len() {
-                if (main_node.polygon_index == polygon_index
-                    && main_node.line_indicies.contains(&(line_index - 1)))
-                    || (other_node.polygon_index == polygon_index
-                        && other_node.line_indicies.contains(&(line_index - 1)))
-                {
-                    continue 'polygon_lines;
-                }
+        pair_counts.candidates += 1;
 
-                let start = polygon.points[line_index - 1];
-                let end = polygon.points[line_index];
+        // Skip nodes that are too far away before doing any collision work
+        if !jump_solver.in_reach(main_node.position, other_node.position) {
+            continue;
+        }
 
-                let line_normal = Vec2::new(-end.y + start.y, end.x - start.x).normalize();
+        pair_counts.in_reach += 1;
 
-                let radius = 4.0;
+        let Some(connection) = jumpable_connection(main_node, other_node, level, jump_solver)
+        else {
+            continue;
+        };
 
-                let offset_lines = [
-                    (start + line_normal * radius, end + line_normal * radius),
-                    (start - line_normal * radius, end - line_normal * radius),
-                ];
+        pair_counts.jumpable += 1;
 
-                for offset_line in offset_lines.iter() {
-                    let intersection =
-                        line_intersect(prev_pos, position, offset_line.0, offset_line.1);
+        jumpable_connections.push(connection);
+    }
 
-                    if intersection.is_some() {
-                        return None;
-                    }
-                }
-            }
-        }
+    (jumpable_connections, pair_counts)
+}
 
-        prev_pos = position;
+/// The connection for jumping from one node to another, if the trajectory doesn't hit the level.
+/// This doesn't check the nodes are in reach, do that first since it's much cheaper.
+pub(crate) fn jumpable_connection(
+    main_node: &PathfindingGraphNode,
+    other_node: &PathfindingGraphNode,
+    level: &Level,
+    jump_solver: &JumpSolver,
+) -> Option<PathfindingGraphConnection> {
+    let mut ignored_lines = main_node.polygon_lines();
+    ignored_lines.extend(other_node.polygon_lines());
+
+    let jump = jump_solver.solve(
+        main_node.position,
+        other_node.position,
+        level,
+        &ignored_lines,
+    );
+
+    if !jump.feasible {
+        return None;
     }
 
-    return Some(launch_velocity.length());
+    Some(PathfindingGraphConnection {
+        node_id: other_node.id,
+        dist: (main_node.position - other_node.position).length(),
+        connection_type: PathfindingGraphConnectionType::Jumpable,
+        effort: jump.launch_velocity.length(),
+        jump: Some(JumpLaunch {
+            launch_velocity: jump.launch_velocity,
+            flight_time: jump.flight_time,
+            hold_time: jump.hold_time,
+            steering: jump.steering,
+        }),
+        drop_velocity: None,
+    })
 }
 
 pub fn calculate_normals(pathfinding: &mut Pathfinding, level: &Level) {
@@ -424,36 +715,453 @@ pub fn setup_corners(pathfinding: &mut Pathfinding) {
     }
 }
 
-// pub fn make_droppable_connections(pathfinding: &mut Pathfinding, level: &Level) {
-//     // For each node
+/// Lines whose normal points at least this far against gravity count as floors that can be landed on
+const FLOOR_NORMAL_MIN_UP: f32 = 0.1;
+
+/// How far past the edge of a ledge the character starts falling, so it doesn't start out touching the wall below
+const LEDGE_CLEARANCE: f32 = 1.0;
+
+/// Connects ledges to the floors the character lands on after walking or running off of them
+pub fn make_droppable_connections(
+    pathfinding: &mut Pathfinding,
+    level: &Level,
+    profile: &JumpProfile,
+) {
+    let gravity_strength = profile.gravity.length();
+    if gravity_strength == 0.0 {
+        return;
+    }
+
+    let jump_solver = JumpSolver::new(profile);
+
+    // Floors and walls are relative to gravity, which doesn't have to point down the screen
+    let up = -profile.gravity / gravity_strength;
+    let across = up.perp();
+
+    // Nothing can fall further than the level is deep along gravity
+    let level_extent = level.size * level.grid_size;
+    let level_depth = level_extent.x * up.x.abs() + level_extent.y * up.y.abs();
+    let max_fall_time = (2.0 * level_depth / gravity_strength).sqrt();
+
+    for i in 0..pathfinding.nodes.len() {
+        let node = &pathfinding.nodes[i];
+
+        // Ledges are the external corners between a floor and the wall going down from it
+        if node.is_external_corner != Some(true) {
+            continue;
+        }
+
+        let line_normals: Vec<Vec2> = node
+            .line_indicies
+            .iter()
+            .filter_map(|&line_index| level.get_line_normal(node.polygon_index, line_index))
+            .collect();
+
+        if !line_normals
+            .iter()
+            .any(|line_normal| line_normal.dot(up) > FLOOR_NORMAL_MIN_UP)
+        {
+            continue;
+        }
 
-//     for i in 0..pathfinding.nodes.len() {
-//         let node = &pathfinding.nodes[i];
+        let Some(wall_normal) = line_normals.iter().find(|line_normal| {
+            line_normal.dot(up) <= FLOOR_NORMAL_MIN_UP && line_normal.dot(across) != 0.0
+        }) else {
+            continue;
+        };
+
+        // Walk off the ledge in the direction the wall faces
+        let walk_off_dir = across * wall_normal.dot(across).signum();
+        let start_pos = node.position + walk_off_dir * (profile.character_radius + LEDGE_CLEARANCE);
 
-//         if node.normal.y > 0.1 {
-//             continue;
-//         }
+        let ignored_lines = node.polygon_lines();
 
-//         let mut droppable_connections: Vec<usize> = Vec::new();
+        let mut droppable_connections: Vec<PathfindingGraphConnection> = Vec::new();
 
-//         // Boxcast down
+        for walk_off_speed in [0.0, profile.run_speed] {
+            let fall = JumpLaunch {
+                launch_velocity: walk_off_dir * walk_off_speed,
+                flight_time: max_fall_time,
+                hold_time: None,
+                steering: None,
+            };
 
-//         // // For each line in the level
-//         // for polygon_index in 0..level.polygons.len() {
-//         //     let polygon = &level.polygons[polygon_index];
+            let landing = jump_solver.first_collision(start_pos, &fall, level, &ignored_lines);
 
-//         //     for line_index in 1..polygon.points.len() {
-//         //         let start = polygon.points[line_index - 1];
-//         //         let end = polygon.points[line_index];
+            let Some(landing) = landing else {
+                continue;
+            };
 
-//         //         // If the boxcast hits a node below, add it to the droppable connections
-//         //     }
-//         // }
+            // Only floors can be landed on, hitting a wall or a ceiling ends the drop
+            let landed_on_floor = level
+                .get_line_normal(landing.line.polygon_index, landing.line.line_index)
+                .is_some_and(|line_normal| line_normal.dot(up) > FLOOR_NORMAL_MIN_UP);
+            if !landed_on_floor {
+                continue;
+            }
+
+            // Connect to the node on the landing line closest to where the character touched down
+            let landing_node = pathfinding
+                .nodes
+                .iter()
+                .filter(|other_node| {
+                    other_node.polygon_index == landing.line.polygon_index
+                        && other_node.line_indicies.contains(&landing.line.line_index)
+                })
+                .min_by(|a, b| {
+                    (a.position - landing.point)
+                        .length_squared()
+                        .total_cmp(&(b.position - landing.point).length_squared())
+                });
 
-//         // If the boxcast hits a node below, add it to the droppable connections
-//     }
+            let Some(landing_node) = landing_node else {
+                continue;
+            };
 
-//     // Boxcast down
+            if landing_node.id == i
+                || droppable_connections
+                    .iter()
+                    .any(|connection| connection.node_id == landing_node.id)
+            {
+                continue;
+            }
 
-//     // If the boxcast hits a node below, add it to the droppable connections
-// }
+            droppable_connections.push(PathfindingGraphConnection {
+                node_id: landing_node.id,
+                dist: (node.position - landing_node.position).length(),
+                connection_type: PathfindingGraphConnectionType::Droppable,
+                effort: walk_off_speed,
+                jump: None,
+                drop_velocity: Some(fall.launch_velocity),
+            });
+        }
+
+        pathfinding.nodes[i].droppable_connections = droppable_connections;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::DEFAULT_LEVEL_DATA;
+
+    fn default_level() -> Level {
+        Level::from_json(DEFAULT_LEVEL_DATA, 32.0).unwrap()
+    }
+
+    #[test]
+    fn jumpable_connections_match_the_jump_solver() {
+        let level = default_level();
+        let profile = JumpProfile::default();
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &profile, &mut pathfinding);
+
+        let jump_solver = JumpSolver::new(&profile);
+
+        for start_node in &pathfinding.nodes {
+            for goal_node in &pathfinding.nodes {
+                if start_node.polygon_index == goal_node.polygon_index {
+                    continue;
+                }
+
+                let mut ignored_lines = start_node.polygon_lines();
+                ignored_lines.extend(goal_node.polygon_lines());
+
+                let jump = jump_solver.solve(
+                    start_node.position,
+                    goal_node.position,
+                    &level,
+                    &ignored_lines,
+                );
+                let connection = start_node
+                    .jumpable_connections
+                    .iter()
+                    .find(|connection| connection.node_id == goal_node.id);
+
+                assert_eq!(
+                    jump.feasible,
+                    connection.is_some(),
+                    "the solver and the graph disagree on {} to {}",
+                    start_node.id,
+                    goal_node.id
+                );
+            }
+        }
+    }
+
+    fn default_level_grid() -> Vec<Vec<usize>> {
+        serde_json::from_slice(DEFAULT_LEVEL_DATA).unwrap()
+    }
+
+    /// Builds the graph for `grid` with `gravity`, returning every droppable connection with the nodes it's between
+    fn droppable_connections(
+        grid: &[Vec<usize>],
+        gravity: Vec2,
+    ) -> Vec<(
+        PathfindingGraphNode,
+        PathfindingGraphConnection,
+        PathfindingGraphNode,
+    )> {
+        let level = Level::from_json(&serde_json::to_vec(grid).unwrap(), 32.0).unwrap();
+        let profile = JumpProfile {
+            gravity,
+            ..JumpProfile::default()
+        };
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &profile, &mut pathfinding);
+
+        pathfinding
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                node.droppable_connections.iter().map(|connection| {
+                    (
+                        node.clone(),
+                        connection.clone(),
+                        pathfinding.nodes[connection.node_id].clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Checks every drop walks off its ledge across gravity, and lands further along gravity
+    fn check_drops_follow_gravity(grid: &[Vec<usize>], gravity: Vec2) {
+        let run_speed = JumpProfile::default().run_speed;
+        let up = -gravity.normalize();
+
+        let drops = droppable_connections(grid, gravity);
+
+        for (ledge, connection, landing) in &drops {
+            let drop_velocity = connection
+                .drop_velocity
+                .expect("drops record their velocity");
+            let walk_off_speed = drop_velocity.length();
+
+            assert!(
+                drop_velocity.dot(up).abs() < 1e-6,
+                "walked off {} at {}",
+                ledge.position,
+                drop_velocity
+            );
+            assert!(walk_off_speed == 0.0 || (walk_off_speed - run_speed).abs() < 1e-6);
+            assert_eq!(connection.effort, walk_off_speed);
+
+            // Off the side the ledge's corner faces, rather than back over the floor
+            assert!(ledge.normal.dot(drop_velocity) >= 0.0);
+
+            assert!(
+                landing.position.dot(up) < ledge.position.dot(up),
+                "dropped up from {} to {}",
+                ledge.position,
+                landing.position
+            );
+        }
+
+        // Running off has to be recorded as well, not just stepping off
+        assert!(drops
+            .iter()
+            .any(|(_, connection, _)| connection.effort > 0.0));
+    }
+
+    #[test]
+    fn droppable_connections_record_the_walk_off_velocity() {
+        check_drops_follow_gravity(&default_level_grid(), Vec2::new(0.0, -0.5));
+    }
+
+    #[test]
+    fn droppable_connections_follow_sideways_gravity() {
+        let grid = default_level_grid();
+
+        // A quarter turn clockwise turns the floors to face right, and the right triangles with them
+        let rows = grid.len();
+        let turned_grid: Vec<Vec<usize>> = (0..grid[0].len())
+            .map(|row| {
+                (0..rows)
+                    .map(|column| match grid[rows - 1 - column][row] {
+                        2 => 4,
+                        3 => 2,
+                        4 => 5,
+                        5 => 3,
+                        tile => tile,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        check_drops_follow_gravity(&turned_grid, Vec2::new(-0.5, 0.0));
+    }
+
+    /// A graph of unconnected nodes at `positions`, to be wired up with `connect`
+    fn graph(positions: &[(f32, f32)]) -> Pathfinding {
+        let nodes = positions
+            .iter()
+            .enumerate()
+            .map(|(id, &(x, y))| PathfindingGraphNode {
+                id,
+                position: Vec2::new(x, y),
+                polygon_index: id,
+                line_indicies: Vec::new(),
+                walkable_connections: Vec::new(),
+                jumpable_connections: Vec::new(),
+                droppable_connections: Vec::new(),
+                normal: Vec2::Y,
+                is_corner: false,
+                is_external_corner: None,
+            })
+            .collect();
+
+        Pathfinding::from_nodes(nodes)
+    }
+
+    fn connect(
+        pathfinding: &mut Pathfinding,
+        from: usize,
+        to: usize,
+        connection_type: PathfindingGraphConnectionType,
+        effort: f32,
+    ) {
+        let connection = PathfindingGraphConnection {
+            node_id: to,
+            dist: (pathfinding.nodes[to].position - pathfinding.nodes[from].position).length(),
+            connection_type: connection_type.clone(),
+            effort,
+            jump: None,
+            drop_velocity: None,
+        };
+
+        let node = &mut pathfinding.nodes[from];
+        match connection_type {
+            PathfindingGraphConnectionType::Walkable => &mut node.walkable_connections,
+            PathfindingGraphConnectionType::Jumpable => &mut node.jumpable_connections,
+            PathfindingGraphConnectionType::Droppable => &mut node.droppable_connections,
+        }
+        .push(connection);
+    }
+
+    /// The cost of the cheapest path between every node and `start_node_id`, by Dijkstra's algorithm
+    fn cheapest_costs(pathfinding: &Pathfinding, start_node_id: usize) -> Vec<f32> {
+        let mut costs = vec![f32::INFINITY; pathfinding.nodes.len()];
+        let mut done = vec![false; pathfinding.nodes.len()];
+        costs[start_node_id] = 0.0;
+
+        while let Some(node_id) = (0..costs.len())
+            .filter(|&node_id| !done[node_id] && costs[node_id].is_finite())
+            .min_by(|&a, &b| costs[a].total_cmp(&costs[b]))
+        {
+            done[node_id] = true;
+
+            let node = &pathfinding.nodes[node_id];
+            for connection in node
+                .walkable_connections
+                .iter()
+                .chain(&node.jumpable_connections)
+                .chain(&node.droppable_connections)
+            {
+                let cost = costs[node_id] + connection.cost();
+                if cost < costs[connection.node_id] {
+                    costs[connection.node_id] = cost;
+                }
+            }
+        }
+
+        costs
+    }
+
+    #[test]
+    fn find_path_takes_a_cheaper_detour() {
+        use PathfindingGraphConnectionType::*;
+
+        // 0 and 3 are on either side of a gap. Jumping straight across is shortest, but takes a lot of effort,
+        // while 4 looks close to the goal but the drop from it is even harder. Walking around 1 and 2 is cheapest.
+        let mut pathfinding = graph(&[
+            (0.0, 0.0),
+            (0.0, -60.0),
+            (100.0, -60.0),
+            (100.0, 0.0),
+            (60.0, 20.0),
+        ]);
+        connect(&mut pathfinding, 0, 3, Jumpable, 500.0);
+        connect(&mut pathfinding, 0, 4, Jumpable, 10.0);
+        connect(&mut pathfinding, 4, 3, Droppable, 600.0);
+        connect(&mut pathfinding, 0, 1, Walkable, 0.0);
+        connect(&mut pathfinding, 1, 2, Walkable, 0.0);
+        connect(&mut pathfinding, 2, 3, Walkable, 0.0);
+
+        let path = pathfinding
+            .find_path(0, 3)
+            .expect("the goal can be reached");
+
+        assert_eq!(path.node_ids(), vec![0, 1, 2, 3]);
+        assert!((path.cost - 220.0).abs() < 1e-4, "cost {}", path.cost);
+
+        // Without walking the jump straight across is the cheapest way left
+        let path = pathfinding
+            .find_path_with(0, 3, |connection| {
+                !matches!(connection.connection_type, Walkable)
+            })
+            .expect("the goal can be reached without walking");
+
+        assert_eq!(path.node_ids(), vec![0, 3]);
+        assert!((path.cost - 600.0).abs() < 1e-4, "cost {}", path.cost);
+    }
+
+    #[test]
+    fn find_path_returns_none_when_the_goal_cannot_be_reached() {
+        use PathfindingGraphConnectionType::*;
+
+        // 3 can only be left, never reached
+        let mut pathfinding = graph(&[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0), (150.0, 0.0)]);
+        connect(&mut pathfinding, 0, 1, Walkable, 0.0);
+        connect(&mut pathfinding, 1, 0, Walkable, 0.0);
+        connect(&mut pathfinding, 1, 2, Jumpable, 30.0);
+        connect(&mut pathfinding, 3, 2, Walkable, 0.0);
+
+        assert!(pathfinding.find_path(0, 3).is_none());
+        // Only connections going one way
+        assert!(pathfinding.find_path(2, 0).is_none());
+        // Only through a connection the filter leaves out
+        assert!(pathfinding
+            .find_path_with(0, 2, |connection| {
+                !matches!(connection.connection_type, Jumpable)
+            })
+            .is_none());
+        assert!(pathfinding.find_path(0, 4).is_none());
+
+        assert!(pathfinding.find_path(0, 2).is_some());
+    }
+
+    #[test]
+    fn find_path_costs_match_dijkstra_on_the_default_level() {
+        let level = default_level();
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &JumpProfile::default(), &mut pathfinding);
+
+        for start_node_id in (0..pathfinding.nodes.len()).step_by(7) {
+            let costs = cheapest_costs(&pathfinding, start_node_id);
+
+            for (goal_node_id, &cost) in costs.iter().enumerate() {
+                let path = pathfinding.find_path(start_node_id, goal_node_id);
+
+                match path {
+                    Some(path) => {
+                        assert!(
+                            (path.cost - cost).abs() <= cost * 1e-4 + 1e-3,
+                            "{} to {} cost {} rather than {}",
+                            start_node_id,
+                            goal_node_id,
+                            path.cost,
+                            cost
+                        );
+
+                        // The hops add up to the cost, and end at the goal
+                        let hop_costs: f32 = path.connections.iter().map(|c| c.cost()).sum();
+                        assert!((hop_costs - path.cost).abs() <= cost * 1e-4 + 1e-3);
+                        assert_eq!(*path.node_ids().last().unwrap(), goal_node_id);
+                    }
+                    None => assert!(cost.is_infinite(), "{} to {}", start_node_id, goal_node_id),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use glam::Vec2;
+
+use crate::level::{Polygon, PolygonLine};
+
+/// The most straight pieces a trajectory is split into when looking up the lines near it
+const MAX_TRAJECTORY_PIECES: f32 = 64.0;
+
+/// A uniform grid over the level lines, so collision queries only test the lines close to them
+/// instead of every line in the level
+pub struct SegmentGrid {
+    cell_size: f32,
+    /// The bottom left corner of the first cell
+    origin: Vec2,
+    columns: usize,
+    rows: usize,
+    /// The indices into `lines` of every line passing through each cell, row by row
+    cells: Vec<Vec<usize>>,
+    lines: Vec<PolygonLine>,
+}
+
+impl SegmentGrid {
+    /// Sorts every line of every polygon into square cells of `cell_size`
+    pub fn new(polygons: &[Polygon], cell_size: f32) -> SegmentGrid {
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+
+        for point in polygons.iter().flat_map(|polygon| polygon.points.iter()) {
+            min = min.min(*point);
+            max = max.max(*point);
+        }
+
+        if min.x > max.x {
+            min = Vec2::ZERO;
+            max = Vec2::ZERO;
+        }
+
+        let mut grid = SegmentGrid {
+            cell_size,
+            origin: min,
+            columns: ((max.x - min.x) / cell_size).floor() as usize + 1,
+            rows: ((max.y - min.y) / cell_size).floor() as usize + 1,
+            cells: Vec::new(),
+            lines: Vec::new(),
+        };
+        grid.cells = vec![Vec::new(); grid.columns * grid.rows];
+
+        for (polygon_index, polygon) in polygons.iter().enumerate() {
+            for line_index in 1..polygon.points.len() {
+                let line_id = grid.lines.len();
+                grid.lines.push(PolygonLine {
+                    polygon_index,
+                    line_index: line_index - 1,
+                });
+
+                let cells = grid.cells_along_segment(
+                    polygon.points[line_index - 1],
+                    polygon.points[line_index],
+                    0.0,
+                );
+
+                for cell in cells {
+                    grid.cells[cell].push(line_id);
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// Every line that could be within `radius` of the segment from `start` to `end`.
+    /// This is a broad phase, some of the lines returned may not actually be that close.
+    pub fn lines_near_segment(&self, start: Vec2, end: Vec2, radius: f32) -> Vec<PolygonLine> {
+        let line_ids = self
+            .cells_along_segment(start, end, radius)
+            .into_iter()
+            .flat_map(|cell| self.cells[cell].iter().copied())
+            .collect();
+
+        self.unique_lines(line_ids)
+    }
+
+    /// Every line that could be within `radius` of the parabola `start_pos + velocity * t + acceleration * t^2 / 2`
+    /// for `t` in `[0, max_t]`. This is a broad phase, some of the lines returned may not actually be that close.
+    pub fn lines_near_trajectory(
+        &self,
+        start_pos: Vec2,
+        velocity: Vec2,
+        acceleration: Vec2,
+        radius: f32,
+        max_t: f32,
+    ) -> Vec<PolygonLine> {
+        let position_at = |t: f32| start_pos + velocity * t + acceleration * t * t / 2.0;
+
+        // Split the parabola into straight pieces that stray at most half a cell from the curve
+        let piece_count = (max_t * (acceleration.length() / (4.0 * self.cell_size)).sqrt())
+            .ceil()
+            .clamp(1.0, MAX_TRAJECTORY_PIECES);
+        let piece_time = max_t / piece_count;
+        // A piece of a parabola is never further than |a| * h^2 / 8 from its chord
+        let sagitta = acceleration.length() * piece_time * piece_time / 8.0;
+
+        let line_ids = (0..piece_count as usize)
+            .flat_map(|i| {
+                self.cells_along_segment(
+                    position_at(piece_time * i as f32),
+                    position_at(piece_time * (i + 1) as f32),
+                    radius + sagitta,
+                )
+            })
+            .flat_map(|cell| self.cells[cell].iter().copied())
+            .collect();
+
+        self.unique_lines(line_ids)
+    }
+
+    fn unique_lines(&self, mut line_ids: Vec<usize>) -> Vec<PolygonLine> {
+        line_ids.sort_unstable();
+        line_ids.dedup();
+
+        line_ids
+            .into_iter()
+            .map(|line_id| self.lines[line_id].clone())
+            .collect()
+    }
+
+    /// The cells that any point within `radius` of the segment could be in
+    fn cells_along_segment(&self, start: Vec2, end: Vec2, radius: f32) -> Vec<usize> {
+        let (min, max) = (start.min(end), start.max(end));
+        let mut cells = Vec::new();
+
+        let first_column = self.column_at(min.x - radius);
+        let last_column = self.column_at(max.x + radius);
+
+        for column in first_column..=last_column {
+            let column_left = self.origin.x + column as f32 * self.cell_size;
+
+            // The part of the segment within `radius` of the column, sideways
+            let x_from = (column_left - radius).clamp(min.x, max.x);
+            let x_to = (column_left + self.cell_size + radius).clamp(min.x, max.x);
+
+            let (y_from, y_to) = if end.x == start.x {
+                (min.y, max.y)
+            } else {
+                let y_at = |x: f32| start.y + (end.y - start.y) * (x - start.x) / (end.x - start.x);
+                (y_at(x_from).min(y_at(x_to)), y_at(x_from).max(y_at(x_to)))
+            };
+
+            let first_row = self.row_at(y_from - radius);
+            let last_row = self.row_at(y_to + radius);
+
+            for row in first_row..=last_row {
+                cells.push(row * self.columns + column);
+            }
+        }
+
+        cells
+    }
+
+    fn column_at(&self, x: f32) -> usize {
+        (((x - self.origin.x) / self.cell_size).floor().max(0.0) as usize).min(self.columns - 1)
+    }
+
+    fn row_at(&self, y: f32) -> usize {
+        (((y - self.origin.y) / self.cell_size).floor().max(0.0) as usize).min(self.rows - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{closest_point_on_line, line_intersect};
+
+    const CELL_SIZE: f32 = 10.0;
+
+    /// Long lines at odd angles so almost every one of them crosses several cell boundaries,
+    /// with corners that don't land on the cell grid
+    fn polygons() -> Vec<Polygon> {
+        let polygon = |points: &[(f32, f32)]| Polygon {
+            points: points.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+            color: [1.0, 1.0, 1.0],
+            is_container: false,
+        };
+
+        vec![
+            polygon(&[(3.0, 4.0), (97.0, 61.0), (52.5, 88.0), (3.0, 4.0)]),
+            polygon(&[(-20.3, 47.0), (-20.3, -33.1), (71.7, -33.1)]),
+            polygon(&[(40.0, 20.0), (45.0, 22.5)]),
+            polygon(&[(-15.0, 95.0), (29.9, 10.1), (60.0, 99.9), (88.8, -30.0)]),
+        ]
+    }
+
+    /// Every line of `polygons` with the points it runs between
+    fn all_lines(polygons: &[Polygon]) -> Vec<(PolygonLine, Vec2, Vec2)> {
+        polygons
+            .iter()
+            .enumerate()
+            .flat_map(|(polygon_index, polygon)| {
+                polygon
+                    .points
+                    .windows(2)
+                    .enumerate()
+                    .map(move |(line_index, points)| {
+                        let line = PolygonLine {
+                            polygon_index,
+                            line_index,
+                        };
+                        (line, points[0], points[1])
+                    })
+            })
+            .collect()
+    }
+
+    fn segment_distance(a_start: Vec2, a_end: Vec2, b_start: Vec2, b_end: Vec2) -> f32 {
+        if line_intersect(a_start, a_end, b_start, b_end).is_some() {
+            return 0.0;
+        }
+
+        [
+            a_start.distance(closest_point_on_line(b_start, b_end, a_start)),
+            a_end.distance(closest_point_on_line(b_start, b_end, a_end)),
+            b_start.distance(closest_point_on_line(a_start, a_end, b_start)),
+            b_end.distance(closest_point_on_line(a_start, a_end, b_end)),
+        ]
+        .into_iter()
+        .fold(f32::INFINITY, f32::min)
+    }
+
+    /// A fixed sequence of points spread over and past the edges of the polygons
+    fn query_points(count: usize) -> Vec<Vec2> {
+        let mut state: u32 = 12345;
+        let mut next = move || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+
+        (0..count)
+            .map(|_| Vec2::new(next() * 160.0 - 50.0, next() * 170.0 - 60.0))
+            .collect()
+    }
+
+    #[test]
+    fn lines_near_segment_finds_every_line_in_reach() {
+        let polygons = polygons();
+        let grid = SegmentGrid::new(&polygons, CELL_SIZE);
+        let lines = all_lines(&polygons);
+        let points = query_points(400);
+
+        for (query, ends) in points.chunks(2).enumerate() {
+            let (start, end) = (ends[0], ends[1]);
+            // Straight along each axis as well as at odd angles
+            let end = match query % 4 {
+                0 => Vec2::new(start.x, end.y),
+                1 => Vec2::new(end.x, start.y),
+                _ => end,
+            };
+            let radius = [0.0, 2.5, 13.0][query % 3];
+
+            let candidates = grid.lines_near_segment(start, end, radius);
+
+            for (line, line_start, line_end) in &lines {
+                if segment_distance(start, end, *line_start, *line_end) <= radius {
+                    assert!(
+                        candidates.contains(line),
+                        "{:?} is within {} of {} to {} but was not returned",
+                        line,
+                        radius,
+                        start,
+                        end
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines_near_trajectory_finds_every_line_in_reach() {
+        let polygons = polygons();
+        let grid = SegmentGrid::new(&polygons, CELL_SIZE);
+        let lines = all_lines(&polygons);
+        let points = query_points(200);
+        let accelerations = [Vec2::new(0.0, -60.0), Vec2::new(-35.0, 0.0), Vec2::ZERO];
+
+        for (query, start_and_velocity) in points.chunks(2).enumerate() {
+            let start_pos = start_and_velocity[0];
+            let velocity = start_and_velocity[1] - Vec2::new(30.0, 25.0);
+            let acceleration = accelerations[query % accelerations.len()];
+            let radius = [0.0, 4.0][query % 2];
+            let max_t = 2.5;
+
+            let candidates =
+                grid.lines_near_trajectory(start_pos, velocity, acceleration, radius, max_t);
+
+            // Finely sampled pieces of the parabola stand in for the curve itself
+            let samples: Vec<Vec2> = (0..=500)
+                .map(|i| {
+                    let t = max_t * i as f32 / 500.0;
+                    start_pos + velocity * t + acceleration * t * t / 2.0
+                })
+                .collect();
+
+            for (line, line_start, line_end) in &lines {
+                let in_reach = samples.windows(2).any(|piece| {
+                    segment_distance(piece[0], piece[1], *line_start, *line_end) <= radius
+                });
+
+                if in_reach {
+                    assert!(
+                        candidates.contains(line),
+                        "{:?} is within {} of the trajectory from {} at {} but was not returned",
+                        line,
+                        radius,
+                        start_pos,
+                        velocity
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use glam::Vec2;
+use serde::Serialize;
+
+use crate::{
+    jump_profile::JumpProfile,
+    jump_solver::{JumpLaunch, JumpSolver},
+    level::{Level, PolygonLine},
+    pathfinding::{
+        jumpable_connection, Pathfinding, PathfindingGraphConnection,
+        PathfindingGraphConnectionType, PathfindingGraphNode,
+    },
+    utils::closest_point_on_line,
+};
+
+/// How far a position can be from the level surface and still be snapped onto it
+pub const MAX_SURFACE_SNAP_DIST: f32 = 32.0;
+
+/// Leeway for jumps right on the movement model's limits, which can come out a hair over them
+const LAUNCH_SPEED_TOLERANCE: f32 = 1e-3;
+
+/// Positions closer than this to an existing node use that node instead of a temporary one
+const SNAP_TO_NODE_DIST: f32 = 1.0;
+
+/// A path between two positions on the level surface, which don't have to be graph nodes
+#[derive(Debug, Clone, Serialize)]
+pub struct SurfacePath {
+    /// Where the start position was snapped onto the surface
+    pub start_position: Vec2,
+    /// Where the goal position was snapped onto the surface
+    pub goal_position: Vec2,
+    pub hops: Vec<SurfacePathHop>,
+    pub cost: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SurfacePathHop {
+    /// The graph node this hop ends on, or `None` when it ends on the goal position between nodes
+    pub node_id: Option<usize>,
+    pub position: Vec2,
+    pub connection_type: PathfindingGraphConnectionType,
+    pub dist: f32,
+    pub effort: f32,
+    /// How to make the jump, for jumpable hops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump: Option<JumpLaunch>,
+    /// The velocity to walk or run off the ledge with, for droppable hops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drop_velocity: Option<Vec2>,
+}
+
+impl Pathfinding {
+    /// Finds the cheapest path between two positions, like where characters are standing.
+    /// Each position is snapped onto the closest line with nodes on it, where a temporary node is added
+    /// for the path to start or end on. The graph is left as it was afterwards.
+    ///
+    /// `profile` can be weaker than the one the graph was built with, jumps outside its movement limits
+    /// are skipped. It should share the graph's gravity and character radius.
+    pub fn find_path_between_points(
+        &mut self,
+        level: &Level,
+        profile: &JumpProfile,
+        start_position: Vec2,
+        goal_position: Vec2,
+    ) -> Option<SurfacePath> {
+        let graph_node_count = self.nodes.len();
+
+        let path =
+            self.find_path_with_temporary_nodes(level, profile, start_position, goal_position);
+
+        // Remove the temporary nodes and every connection to them
+        self.nodes.truncate(graph_node_count);
+        for node in &mut self.nodes {
+            node.walkable_connections
+                .retain(|connection| connection.node_id < graph_node_count);
+            node.jumpable_connections
+                .retain(|connection| connection.node_id < graph_node_count);
+        }
+
+        path
+    }
+
+    fn find_path_with_temporary_nodes(
+        &mut self,
+        level: &Level,
+        profile: &JumpProfile,
+        start_position: Vec2,
+        goal_position: Vec2,
+    ) -> Option<SurfacePath> {
+        let graph_node_count = self.nodes.len();
+
+        let start_node_id = self.snap_to_surface(level, start_position)?;
+        let goal_node_id = self.snap_to_surface(level, goal_position)?;
+
+        let jump_solver = JumpSolver::new(profile);
+
+        // Jumps out of a temporary start node
+        if start_node_id >= graph_node_count {
+            let start_node = &self.nodes[start_node_id];
+
+            let jumpable_connections = self
+                .nodes
+                .iter()
+                .filter(|other_node| {
+                    other_node.polygon_index != start_node.polygon_index
+                        && jump_solver.in_reach(start_node.position, other_node.position)
+                })
+                .filter_map(|other_node| {
+                    jumpable_connection(start_node, other_node, level, &jump_solver)
+                })
+                .collect();
+
+            self.nodes[start_node_id].jumpable_connections = jumpable_connections;
+        }
+
+        // Jumps into a temporary goal node, the one from the start node was added above
+        if goal_node_id >= graph_node_count {
+            for other_node_id in 0..graph_node_count {
+                let (other_node, goal_node) =
+                    (&self.nodes[other_node_id], &self.nodes[goal_node_id]);
+
+                if other_node.polygon_index == goal_node.polygon_index
+                    || !jump_solver.in_reach(other_node.position, goal_node.position)
+                {
+                    continue;
+                }
+
+                if let Some(connection) =
+                    jumpable_connection(other_node, goal_node, level, &jump_solver)
+                {
+                    self.nodes[other_node_id]
+                        .jumpable_connections
+                        .push(connection);
+                }
+            }
+        }
+
+        let path = self.find_path_with(start_node_id, goal_node_id, |connection| {
+            connection
+                .jump
+                .is_none_or(|jump| jump_solver.allows_launch(&jump, LAUNCH_SPEED_TOLERANCE))
+        })?;
+
+        let hops = path
+            .connections
+            .iter()
+            .map(|connection| SurfacePathHop {
+                node_id: (connection.node_id < graph_node_count).then_some(connection.node_id),
+                position: self.nodes[connection.node_id].position,
+                connection_type: connection.connection_type.clone(),
+                dist: connection.dist,
+                effort: connection.effort,
+                jump: connection.jump,
+                drop_velocity: connection.drop_velocity,
+            })
+            .collect();
+
+        Some(SurfacePath {
+            start_position: self.nodes[start_node_id].position,
+            goal_position: self.nodes[goal_node_id].position,
+            hops,
+            cost: path.cost,
+        })
+    }
+
+    /// Snaps a position onto the closest line with nodes on it, returning the node there.
+    /// When there isn't one already a temporary node is added, walkable to its neighbours on the line.
+    fn snap_to_surface(&mut self, level: &Level, position: Vec2) -> Option<usize> {
+        let mut nearby_lines: Vec<(PolygonLine, Vec2, f32)> = level
+            .lines_near_segment(position, position, MAX_SURFACE_SNAP_DIST)
+            .into_iter()
+            .filter_map(|line| {
+                let (&line_start, &line_end) =
+                    level.get_line(line.polygon_index, line.line_index)?;
+                let snapped_position = closest_point_on_line(line_start, line_end, position);
+                let dist = (snapped_position - position).length();
+
+                (dist <= MAX_SURFACE_SNAP_DIST).then_some((line, snapped_position, dist))
+            })
+            .collect();
+        nearby_lines.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        for (line, snapped_position, _) in nearby_lines {
+            let (&line_start, &line_end) = level.get_line(line.polygon_index, line.line_index)?;
+            let line_dir = (line_end - line_start).normalize_or_zero();
+            let along_line = |point: Vec2| line_dir.dot(point - line_start);
+            let snapped_along_line = along_line(snapped_position);
+
+            // The closest nodes on the line either side of the snapped position
+            let mut previous_node: Option<(usize, f32)> = None;
+            let mut next_node: Option<(usize, f32)> = None;
+
+            for node in &self.nodes {
+                if node.polygon_index != line.polygon_index
+                    || !node.line_indicies.contains(&line.line_index)
+                {
+                    continue;
+                }
+
+                if (node.position - snapped_position).length() < SNAP_TO_NODE_DIST {
+                    return Some(node.id);
+                }
+
+                let node_along_line = along_line(node.position);
+
+                if node_along_line <= snapped_along_line {
+                    if previous_node.is_none_or(|(_, previous)| node_along_line > previous) {
+                        previous_node = Some((node.id, node_along_line));
+                    }
+                } else if next_node.is_none_or(|(_, next)| node_along_line < next) {
+                    next_node = Some((node.id, node_along_line));
+                }
+            }
+
+            // Lines without nodes on them can't be walked on
+            if previous_node.is_none() && next_node.is_none() {
+                continue;
+            }
+
+            let node_id = self.nodes.len();
+            let mut temporary_node = PathfindingGraphNode {
+                id: node_id,
+                position: snapped_position,
+                polygon_index: line.polygon_index,
+                line_indicies: vec![line.line_index],
+                walkable_connections: Vec::new(),
+                jumpable_connections: Vec::new(),
+                droppable_connections: Vec::new(),
+                normal: level
+                    .get_line_normal(line.polygon_index, line.line_index)
+                    .unwrap_or(Vec2::ZERO),
+                is_corner: false,
+                is_external_corner: None,
+            };
+
+            for (neighbour_id, _) in previous_node.into_iter().chain(next_node) {
+                let dist = (self.nodes[neighbour_id].position - snapped_position).length();
+
+                temporary_node
+                    .walkable_connections
+                    .push(PathfindingGraphConnection {
+                        node_id: neighbour_id,
+                        dist,
+                        connection_type: PathfindingGraphConnectionType::Walkable,
+                        effort: 0.0,
+                        jump: None,
+                        drop_velocity: None,
+                    });
+                self.nodes[neighbour_id]
+                    .walkable_connections
+                    .push(PathfindingGraphConnection {
+                        node_id,
+                        dist,
+                        connection_type: PathfindingGraphConnectionType::Walkable,
+                        effort: 0.0,
+                        jump: None,
+                        drop_velocity: None,
+                    });
+            }
+
+            self.nodes.push(temporary_node);
+
+            return Some(node_id);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        jump_profile::MovementModel, level::DEFAULT_LEVEL_DATA, pathfinding::init_pathfinding_graph,
+    };
+
+    #[test]
+    fn jumpable_hops_carry_their_jump() {
+        let level = Level::from_json(DEFAULT_LEVEL_DATA, 32.0).unwrap();
+        let profile = JumpProfile {
+            movement: MovementModel::VariableHeight {
+                jump_speed: 7.0,
+                max_horizontal_speed: 3.0,
+                min_hold_time: 2.0,
+                max_hold_time: 12.0,
+                release_gravity_multiplier: 2.5,
+            },
+            ..JumpProfile::default()
+        };
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &profile, &mut pathfinding);
+
+        // Climbing as far as the highest jump in the graph needs a jump somewhere along the way
+        let (start_node, goal_node) = pathfinding
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                node.jumpable_connections
+                    .iter()
+                    .map(move |connection| (node, connection.node_id))
+            })
+            .map(|(node, goal_node_id)| (node.clone(), pathfinding.nodes[goal_node_id].clone()))
+            .max_by(|a, b| {
+                (a.1.position.y - a.0.position.y).total_cmp(&(b.1.position.y - b.0.position.y))
+            })
+            .expect("the built in level has jumps");
+
+        let path = pathfinding
+            .find_path_between_points(&level, &profile, start_node.position, goal_node.position)
+            .expect("the jump connects them");
+
+        let jumpable_hops: Vec<&SurfacePathHop> = path
+            .hops
+            .iter()
+            .filter(|hop| {
+                matches!(
+                    hop.connection_type,
+                    PathfindingGraphConnectionType::Jumpable
+                )
+            })
+            .collect();
+        assert!(!jumpable_hops.is_empty());
+
+        for hop in jumpable_hops {
+            let jump = hop.jump.expect("jumpable hops say how to jump");
+            assert!(jump
+                .hold_time
+                .is_some_and(|hold_time| (2.0..=12.0).contains(&hold_time)));
+            assert!(jump.flight_time > 0.0);
+        }
+    }
+
+    /// Halfway along each walkable connection, which is on the surface but between nodes
+    fn between_nodes(pathfinding: &Pathfinding) -> Vec<Vec2> {
+        pathfinding
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                node.walkable_connections.iter().map(|connection| {
+                    (node.position + pathfinding.nodes[connection.node_id].position) / 2.0
+                })
+            })
+            .filter(|position| {
+                pathfinding
+                    .nodes
+                    .iter()
+                    .all(|node| node.position.distance(*position) >= SNAP_TO_NODE_DIST)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_path_between_points_removes_its_temporary_nodes() {
+        let level = Level::from_json(DEFAULT_LEVEL_DATA, 32.0).unwrap();
+        let profile = JumpProfile::default();
+        let mut pathfinding = Pathfinding::default();
+        init_pathfinding_graph(&level, &profile, &mut pathfinding);
+
+        let graph_before = serde_json::to_value(&pathfinding.nodes).unwrap();
+        let node_count = pathfinding.nodes.len();
+
+        let positions = between_nodes(&pathfinding);
+        let start_position = positions[0];
+        let goal_position = *positions.last().unwrap();
+
+        let path = pathfinding
+            .find_path_between_points(&level, &profile, start_position, goal_position)
+            .expect("both positions are on the surface of the built in level");
+
+        // Both ends were on temporary nodes
+        assert!(path.hops.last().unwrap().node_id.is_none());
+        assert!(pathfinding
+            .nodes
+            .iter()
+            .all(|node| node.position != path.start_position));
+
+        assert_eq!(pathfinding.nodes.len(), node_count);
+        assert_eq!(
+            serde_json::to_value(&pathfinding.nodes).unwrap(),
+            graph_before
+        );
+    }
+}
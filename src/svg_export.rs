@@ -0,0 +1,171 @@
+use std::fmt::{self, Write};
+
+use glam::Vec2;
+
+use crate::{
+    jump_profile::JumpProfile, jump_solver::JumpSolver, level::Level, pathfinding::Pathfinding,
+};
+
+/// Extra space left around the level in the exported image
+const SVG_MARGIN: f32 = 16.0;
+
+/// Draws the level polygons and the navigation graph as an SVG image.
+/// Walkable connections are solid lines, jumpable ones follow their parabolas and droppable ones are dashed.
+/// `profile` should be the one the graph was built with, it shapes the jumps that are held for a while.
+pub fn export_svg(level: &Level, profile: &JumpProfile, pathfinding: &Pathfinding) -> String {
+    let mut svg = String::new();
+
+    write_svg(&mut svg, level, profile, pathfinding).expect("Writing to a String can't fail");
+
+    svg
+}
+
+fn write_svg(
+    svg: &mut String,
+    level: &Level,
+    profile: &JumpProfile,
+    pathfinding: &Pathfinding,
+) -> fmt::Result {
+    let jump_solver = JumpSolver::new(profile);
+
+    let mut min = Vec2::splat(f32::INFINITY);
+    let mut max = Vec2::splat(f32::NEG_INFINITY);
+
+    for point in level
+        .polygons
+        .iter()
+        .flat_map(|polygon| polygon.points.iter())
+    {
+        min = min.min(*point);
+        max = max.max(*point);
+    }
+
+    if min.x > max.x {
+        min = Vec2::ZERO;
+        max = Vec2::ZERO;
+    }
+
+    min -= Vec2::splat(SVG_MARGIN);
+    max += Vec2::splat(SVG_MARGIN);
+
+    // SVG's y axis points down, so every point is flipped
+    let point = |position: Vec2| format!("{:.2},{:.2}", position.x, -position.y);
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:.2} {:.2} {:.2} {:.2}">"#,
+        min.x,
+        -max.y,
+        max.x - min.x,
+        max.y - min.y
+    )?;
+    writeln!(
+        svg,
+        r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="black"/>"#,
+        min.x,
+        -max.y,
+        max.x - min.x,
+        max.y - min.y
+    )?;
+
+    // Polygon colors are random, so they're all drawn in grey to keep exports of the same level identical
+    writeln!(
+        svg,
+        r#"<g id="polygons" stroke="grey" stroke-width="1" fill="grey" fill-opacity="0.1">"#
+    )?;
+    for polygon in &level.polygons {
+        let points: Vec<String> = polygon.points.iter().map(|&p| point(p)).collect();
+
+        writeln!(svg, r#"<polyline points="{}"/>"#, points.join(" "))?;
+    }
+    writeln!(svg, "</g>")?;
+
+    writeln!(
+        svg,
+        r#"<g id="walkable-connections" stroke="cyan" stroke-width="1">"#
+    )?;
+    for node in &pathfinding.nodes {
+        for connection in &node.walkable_connections {
+            // Walkable connections go both ways, so only draw each one once
+            if connection.node_id < node.id {
+                continue;
+            }
+
+            let other_position = pathfinding.nodes[connection.node_id].position;
+
+            writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                node.position.x, -node.position.y, other_position.x, -other_position.y
+            )?;
+        }
+    }
+    writeln!(svg, "</g>")?;
+
+    writeln!(
+        svg,
+        r#"<g id="jumpable-connections" stroke="orange" stroke-width="0.5" stroke-opacity="0.5" fill="none">"#
+    )?;
+    for node in &pathfinding.nodes {
+        for connection in &node.jumpable_connections {
+            let goal_position = pathfinding.nodes[connection.node_id].position;
+            let Some(jump) = connection.jump else {
+                continue;
+            };
+
+            // Jumps that are released early or steered are made of several parabolas
+            let beziers = jump_solver.trajectory_beziers(node.position, &jump);
+
+            let mut path = format!("M {}", point(node.position));
+            for (i, [_, control_point, end_point]) in beziers.iter().enumerate() {
+                // End exactly on the goal node, rather than wherever rounding puts the end of the last curve
+                let end_point = if i + 1 == beziers.len() {
+                    goal_position
+                } else {
+                    *end_point
+                };
+
+                write!(path, " Q {} {}", point(*control_point), point(end_point))?;
+            }
+
+            writeln!(svg, r#"<path d="{}"/>"#, path)?;
+        }
+    }
+    writeln!(svg, "</g>")?;
+
+    writeln!(
+        svg,
+        r#"<g id="droppable-connections" stroke="purple" stroke-width="1" stroke-dasharray="4 2">"#
+    )?;
+    for node in &pathfinding.nodes {
+        for connection in &node.droppable_connections {
+            let other_position = pathfinding.nodes[connection.node_id].position;
+
+            writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                node.position.x, -node.position.y, other_position.x, -other_position.y
+            )?;
+        }
+    }
+    writeln!(svg, "</g>")?;
+
+    // External corners are red, internal corners are yellow and every other node is white
+    writeln!(svg, r#"<g id="nodes">"#)?;
+    for node in &pathfinding.nodes {
+        let color = match (node.is_corner, node.is_external_corner) {
+            (true, Some(true)) => "red",
+            (true, _) => "yellow",
+            (false, _) => "white",
+        };
+
+        writeln!(
+            svg,
+            r#"<circle cx="{:.2}" cy="{:.2}" r="2.5" fill="{}"><title>node {}</title></circle>"#,
+            node.position.x, -node.position.y, color, node.id
+        )?;
+    }
+    writeln!(svg, "</g>")?;
+
+    writeln!(svg, "</svg>")
+}
@@ -1,4 +1,4 @@
-use bevy::math::Vec2;
+use glam::{DVec2, Vec2};
 
 // pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
 //     a + (b - a) * t
@@ -10,6 +10,23 @@ pub fn line_intersect(
     line_2_start: Vec2,
     line_2_end: Vec2,
 ) -> Option<Vec2> {
+    let (t, _) = line_intersect_fractions(line_1_start, line_1_end, line_2_start, line_2_end)?;
+
+    let line_1 = line_1_end - line_1_start;
+
+    Some(Vec2::new(
+        line_1_start.x + t * line_1.x,
+        line_1_start.y + t * line_1.y,
+    ))
+}
+
+/// How far along each of the two lines their intersection is, from 0 at the start to 1 at the end
+pub fn line_intersect_fractions(
+    line_1_start: Vec2,
+    line_1_end: Vec2,
+    line_2_start: Vec2,
+    line_2_end: Vec2,
+) -> Option<(f32, f32)> {
     let line_1 = line_1_end - line_1_start;
     let line_2 = line_2_end - line_2_start;
     let r_cross_s = cross_product(line_1, line_2);
@@ -17,11 +34,8 @@ pub fn line_intersect(
     let t = cross_product(a_to_c, line_2) / r_cross_s;
     let u = cross_product(a_to_c, line_1) / r_cross_s;
 
-    if t >= 0.0 && t <= 1.0 && u >= 0.0 && u <= 1.0 {
-        Some(Vec2::new(
-            line_1_start.x + t * line_1.x,
-            line_1_start.y + t * line_1.y,
-        ))
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some((t, u))
     } else {
         None
     }
@@ -37,3 +51,434 @@ pub fn side_of_line_detection(line_start: Vec2, line_end: Vec2, point: Vec2) ->
 
     return determinant.signum();
 }
+
+/// The point on the segment from `line_start` to `line_end` that is closest to `point`
+pub fn closest_point_on_line(line_start: Vec2, line_end: Vec2, point: Vec2) -> Vec2 {
+    let line = line_end - line_start;
+    let length_squared = line.length_squared();
+
+    if length_squared == 0.0 {
+        return line_start;
+    }
+
+    let t = ((point - line_start).dot(line) / length_squared).clamp(0.0, 1.0);
+
+    line_start + line * t
+}
+
+/// The real roots of `a*t^2 + b*t + c = 0`, falling back to the linear case when `a` is zero
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return Vec::new();
+        }
+
+        return vec![-c / b];
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    // Avoids the cancellation error of the textbook formula when b is large
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+
+    if q == 0.0 {
+        return vec![0.0];
+    }
+
+    vec![q / a, c / q]
+}
+
+/// The real roots of `a*t^3 + b*t^2 + c*t + d = 0`, falling back to the quadratic case when `a` is zero
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return solve_quadratic(b, c, d);
+    }
+
+    let (b, c, d) = (b / a, c / a, d / a);
+
+    // Substitute t = x - b/3 to get the depressed cubic x^3 + px + q = 0
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let offset = -b / 3.0;
+
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    if discriminant > 0.0 {
+        // One real root
+        let discriminant_sqrt = discriminant.sqrt();
+        let u = (-q / 2.0 + discriminant_sqrt).cbrt();
+        let v = (-q / 2.0 - discriminant_sqrt).cbrt();
+
+        vec![u + v + offset]
+    } else if p.abs() < 1e-12 {
+        // One triple root
+        vec![offset]
+    } else {
+        // Three real roots
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+
+        (0..3)
+            .map(|k| 2.0 * r * ((phi + 2.0 * std::f64::consts::PI * k as f64) / 3.0).cos() + offset)
+            .collect()
+    }
+}
+
+/// The earliest root in `[0, max_t]` of the quartic with the given coefficients (highest power first),
+/// assuming it is positive at `t = 0`
+fn first_quartic_root_in_range(coefficients: [f64; 5], max_t: f64) -> Option<f64> {
+    let [c4, c3, c2, c1, c0] = coefficients;
+    let f = |t: f64| (((c4 * t + c3) * t + c2) * t + c1) * t + c0;
+
+    // The quartic is monotonic between its turning points, so each interval holds at most one root
+    let mut boundaries: Vec<f64> = solve_cubic(4.0 * c4, 3.0 * c3, 2.0 * c2, c1)
+        .into_iter()
+        .filter(|t| *t > 0.0 && *t < max_t)
+        .collect();
+    boundaries.sort_by(f64::total_cmp);
+    boundaries.insert(0, 0.0);
+    boundaries.push(max_t);
+
+    for interval in boundaries.windows(2) {
+        let (mut low, mut high) = (interval[0], interval[1]);
+
+        if f(low) <= 0.0 {
+            return Some(low);
+        }
+
+        if f(high) > 0.0 {
+            continue;
+        }
+
+        for _ in 0..64 {
+            let mid = (low + high) / 2.0;
+            if f(mid) > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        return Some(high);
+    }
+
+    None
+}
+
+/// The earliest time in `[0, max_t]` at which a circle following the parabola
+/// `start_pos + velocity * t + acceleration * t^2 / 2` touches the segment from `line_start` to `line_end`.
+/// This is exact, it checks the inside of the segment and both of its endpoints.
+pub fn swept_circle_line_contact(
+    start_pos: Vec2,
+    velocity: Vec2,
+    acceleration: Vec2,
+    radius: f32,
+    max_t: f32,
+    line_start: Vec2,
+    line_end: Vec2,
+) -> Option<f32> {
+    // Work in f64, the quartic coefficients get large over long jumps
+    let (start_pos, velocity, acceleration) = (
+        start_pos.as_dvec2(),
+        velocity.as_dvec2(),
+        acceleration.as_dvec2(),
+    );
+    let (line_start, line_end) = (line_start.as_dvec2(), line_end.as_dvec2());
+    let (radius, max_t) = (radius as f64, max_t as f64);
+
+    let position_at = |t: f64| start_pos + velocity * t + acceleration * t * t / 2.0;
+
+    let line = line_end - line_start;
+    let length = line.length();
+
+    // Already touching at the start of the jump
+    let start_along_line = if length > 0.0 {
+        (line.dot(start_pos - line_start) / (length * length)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let start_closest = line_start + line * start_along_line;
+    if (start_pos - start_closest).length_squared() <= radius * radius {
+        return Some(0.0);
+    }
+
+    let mut earliest: Option<f64> = None;
+
+    // The inside of the segment, where the distance to the line is a quadratic in t
+    if length > 0.0 {
+        let line_dir = line / length;
+        let line_normal = DVec2::new(-line_dir.y, line_dir.x);
+
+        for side in [radius, -radius] {
+            let roots = solve_quadratic(
+                line_normal.dot(acceleration) / 2.0,
+                line_normal.dot(velocity),
+                line_normal.dot(start_pos - line_start) - side,
+            );
+
+            for t in roots {
+                if !(0.0..=max_t).contains(&t) {
+                    continue;
+                }
+
+                let along_line = line_dir.dot(position_at(t) - line_start);
+                if (0.0..=length).contains(&along_line) && earliest.is_none_or(|e| t < e) {
+                    earliest = Some(t);
+                }
+            }
+        }
+    }
+
+    // The endpoints, where the squared distance is a quartic in t
+    for endpoint in [line_start, line_end] {
+        let offset = start_pos - endpoint;
+
+        let coefficients = [
+            acceleration.dot(acceleration) / 4.0,
+            acceleration.dot(velocity),
+            velocity.dot(velocity) + acceleration.dot(offset),
+            2.0 * velocity.dot(offset),
+            offset.dot(offset) - radius * radius,
+        ];
+
+        if let Some(t) = first_quartic_root_in_range(coefficients, max_t) {
+            if earliest.is_none_or(|e| t < e) {
+                earliest = Some(t);
+            }
+        }
+    }
+
+    earliest.map(|t| t as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(f64::total_cmp);
+        roots
+    }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        let roots = sorted(roots);
+
+        assert_eq!(roots.len(), expected.len(), "roots {:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "roots {:?}", roots);
+        }
+    }
+
+    fn assert_contact(contact: Option<f32>, expected: f32) {
+        let contact = contact.expect("the circle should touch the segment");
+
+        assert!((contact - expected).abs() < 1e-4, "contact at {}", contact);
+    }
+
+    #[test]
+    fn solve_quadratic_finds_known_roots() {
+        // (t - 2)(t + 3)
+        assert_roots(solve_quadratic(1.0, 1.0, -6.0), &[-3.0, 2.0]);
+        // (t - 1)^2
+        assert_roots(solve_quadratic(1.0, -2.0, 1.0), &[1.0, 1.0]);
+        // Linear, 2t - 4
+        assert_roots(solve_quadratic(0.0, 2.0, -4.0), &[2.0]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+        assert_roots(solve_quadratic(0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn solve_cubic_finds_known_roots() {
+        // (t - 1)(t - 2)(t - 3)
+        assert_roots(solve_cubic(1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        // (t - 1)(t^2 + t + 1)
+        assert_roots(solve_cubic(1.0, 0.0, 0.0, -1.0), &[1.0]);
+        // (t - 1)^2 (t + 2)
+        assert_roots(solve_cubic(1.0, 0.0, -3.0, 2.0), &[-2.0, 1.0, 1.0]);
+        // (t - 2)^3
+        assert_roots(solve_cubic(1.0, -6.0, 12.0, -8.0), &[2.0]);
+        // Falls back to the quadratic, (t - 2)(t + 3)
+        assert_roots(solve_cubic(0.0, 1.0, 1.0, -6.0), &[-3.0, 2.0]);
+    }
+
+    #[test]
+    fn first_quartic_root_in_range_finds_the_earliest_root() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        let distinct = [1.0, -10.0, 35.0, -50.0, 24.0];
+        let root = first_quartic_root_in_range(distinct, 10.0).unwrap();
+        assert!((root - 1.0).abs() < 1e-9, "root {}", root);
+
+        // Every root is past the end of the range
+        assert_eq!(first_quartic_root_in_range(distinct, 0.5), None);
+    }
+
+    #[test]
+    fn first_quartic_root_in_range_finds_repeated_roots() {
+        // (t - 2)^2 (t - 3)^2 only touches zero
+        let double = [1.0, -10.0, 37.0, -60.0, 36.0];
+        let root = first_quartic_root_in_range(double, 10.0).unwrap();
+        assert!((root - 2.0).abs() < 1e-6, "root {}", root);
+
+        // (t - 1)^4
+        let quadruple = [1.0, -4.0, 6.0, -4.0, 1.0];
+        let root = first_quartic_root_in_range(quadruple, 10.0).unwrap();
+        assert!((root - 1.0).abs() < 1e-3, "root {}", root);
+    }
+
+    #[test]
+    fn first_quartic_root_in_range_ignores_roots_outside_the_range() {
+        // (t + 1)(t + 2)(t - 5)(t - 6) is positive at 0 with two negative roots
+        let quartic = [1.0, -8.0, -1.0, 68.0, 60.0];
+
+        assert_eq!(first_quartic_root_in_range(quartic, 4.0), None);
+
+        let root = first_quartic_root_in_range(quartic, 8.0).unwrap();
+        assert!((root - 5.0).abs() < 1e-9, "root {}", root);
+    }
+
+    #[test]
+    fn swept_circle_hits_the_segment_interior() {
+        let (line_start, line_end) = (Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0));
+
+        // Falling straight down onto the middle of the segment
+        let contact = swept_circle_line_contact(
+            Vec2::new(1.0, 10.0),
+            Vec2::new(0.0, -1.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 9.0);
+
+        // Dropped from rest, y = 10 - t^2 reaches y = 1 at t = 3
+        let contact = swept_circle_line_contact(
+            Vec2::new(-2.0, 10.0),
+            Vec2::ZERO,
+            Vec2::new(0.0, -2.0),
+            1.0,
+            20.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 3.0);
+
+        // The contact is past the end of the sweep
+        let contact = swept_circle_line_contact(
+            Vec2::new(1.0, 10.0),
+            Vec2::new(0.0, -1.0),
+            Vec2::ZERO,
+            1.0,
+            8.0,
+            line_start,
+            line_end,
+        );
+        assert_eq!(contact, None);
+    }
+
+    #[test]
+    fn swept_circle_hits_each_endpoint_cap() {
+        let (line_start, line_end) = (Vec2::new(0.0, 0.0), Vec2::new(5.0, 0.0));
+
+        // Moving along the line into the start cap
+        let contact = swept_circle_line_contact(
+            Vec2::new(-10.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 9.0);
+
+        // Moving along the line into the end cap
+        let contact = swept_circle_line_contact(
+            Vec2::new(15.0, 0.0),
+            Vec2::new(-1.0, 0.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 9.0);
+
+        // Falling past the end, close enough to clip the corner at (5, 0):
+        // x = 5.6, so the cap is reached when y = 0.8, at t = 10 - 0.8
+        let contact = swept_circle_line_contact(
+            Vec2::new(5.6, 10.0),
+            Vec2::new(0.0, -1.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 9.2);
+    }
+
+    #[test]
+    fn swept_circle_misses_a_parallel_segment() {
+        let contact = swept_circle_line_contact(
+            Vec2::new(-10.0, 3.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            Vec2::new(0.0, 0.0),
+            Vec2::new(5.0, 0.0),
+        );
+
+        assert_eq!(contact, None);
+    }
+
+    #[test]
+    fn swept_circle_grazes_a_segment() {
+        let (line_start, line_end) = (Vec2::new(-5.0, 3.0), Vec2::new(5.0, 3.0));
+
+        // y = 2t - t^2/2 peaks at y = 2 when t = 2, just touching the segment one radius above
+        let contact = swept_circle_line_contact(
+            Vec2::ZERO,
+            Vec2::new(0.0, 2.0),
+            Vec2::new(0.0, -1.0),
+            1.0,
+            4.0,
+            line_start,
+            line_end,
+        );
+        assert_contact(contact, 2.0);
+
+        // Peaking a little lower misses it
+        let contact = swept_circle_line_contact(
+            Vec2::new(0.0, -0.1),
+            Vec2::new(0.0, 2.0),
+            Vec2::new(0.0, -1.0),
+            1.0,
+            4.0,
+            line_start,
+            line_end,
+        );
+        assert_eq!(contact, None);
+    }
+
+    #[test]
+    fn swept_circle_touching_at_the_start_hits_immediately() {
+        let contact = swept_circle_line_contact(
+            Vec2::new(0.0, 0.5),
+            Vec2::new(0.0, 1.0),
+            Vec2::ZERO,
+            1.0,
+            20.0,
+            Vec2::new(-5.0, 0.0),
+            Vec2::new(5.0, 0.0),
+        );
+
+        assert_eq!(contact, Some(0.0));
+    }
+}